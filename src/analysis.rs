@@ -0,0 +1,384 @@
+//! Statistics for the "statistics objection".
+//!
+//! Without site guidance every exchange is accepted with the same probability,
+//! so the dynamics only shuffle bits around: the number of true bits is
+//! conserved and, once mixed, every cell is independently true with the global
+//! fill ratio `p`. This module tests the site layer of a run against that
+//! exchange-only null model, comparing cells inside sites with cells outside.
+
+use crate::config::ConfigError;
+use crate::goodness::{matching_bits_on_layer, window_contains, windows_overlap};
+use crate::state::{GameState, Point2};
+use crate::stats::{
+    binomial_test, chi_square_2x2, ks_two_sample, pattern_match_p_value, TestResult,
+};
+use ndarray::*;
+use rand::prelude::*;
+use serde::Serialize;
+use std::fmt;
+
+/// Bit counts of a set of site-layer cells
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct RegionCounts {
+    pub cells: u64,
+    pub ones: u64,
+}
+
+/// Pattern match of one site against its target bitmap
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SiteAnalysis {
+    pub position: Point2,
+    pub is_active: bool,
+    pub matches: u64,
+    pub bits: u64,
+    /// Chance of matching at least as many bits under the null model
+    pub p_value: f64,
+}
+
+/// Comparison of a run with the exchange-only null model
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AnalysisReport {
+    /// Layer the sites were analysed on
+    pub layer: usize,
+    /// Null-model probability of a true bit (the global fill ratio)
+    pub fill_ratio: f64,
+    pub inside: RegionCounts,
+    pub outside: RegionCounts,
+    /// Ones inside sites against Binomial(cells, fill ratio)
+    pub inside_binomial: TestResult,
+    /// Ones outside sites against Binomial(cells, fill ratio)
+    pub outside_binomial: TestResult,
+    /// Independence of bit value and being inside a site
+    pub bit_frequency_chi_square: TestResult,
+    /// Local 3x3 densities inside vs outside sites
+    pub local_density_ks: TestResult,
+    /// Site match scores vs the scores of random reference windows
+    pub pattern_match_ks: TestResult,
+    pub sites: Vec<SiteAnalysis>,
+}
+
+/// Test the site layer of `game_state` against the exchange-only null model
+///
+/// Only flat sites on the configured `site_layer` are tested. `null_windows`
+/// random windows per site provide the reference distribution of
+/// pattern-match scores. They come from the other layers that no site guides,
+/// or if there are none from site-layer windows clear of every site. They are
+/// drawn from a stream derived from the run seed, so the report is
+/// reproducible.
+///
+/// Fails if the site layer is not on the grid or holds no flat site, since
+/// there would be nothing inside sites to compare with.
+pub fn analyze(game_state: &GameState, null_windows: usize) -> Result<AnalysisReport, ConfigError> {
+    let z = game_state.config.site_layer;
+    let depth = game_state.state.dim().2;
+    if z >= depth {
+        return Err(ConfigError(format!(
+            "site_layer {} is not on a grid {} layers deep",
+            z, depth
+        )));
+    }
+    let windows = site_windows(game_state);
+    if windows.is_empty() {
+        return Err(ConfigError(format!(
+            "site_layer {} holds no flat site to analyse",
+            z
+        )));
+    }
+
+    let layer = game_state.state.slice(s![.., .., z]);
+    let fill_ratio = game_state.fill_ratio();
+    let wrap = game_state.config.wrap();
+    let inside_mask = Array2::from_shape_fn(layer.dim(), |point| {
+        windows
+            .iter()
+            .any(|(position, shape)| window_contains(position, *shape, point, layer.dim(), wrap))
+    });
+
+    // Bit frequencies and local densities, split by region
+    let mut inside = RegionCounts { cells: 0, ones: 0 };
+    let mut outside = RegionCounts { cells: 0, ones: 0 };
+    let mut inside_density = Vec::new();
+    let mut outside_density = Vec::new();
+    for ((x, y), &bit) in layer.indexed_iter() {
+        let (counts, densities) = if inside_mask[[x, y]] {
+            (&mut inside, &mut inside_density)
+        } else {
+            (&mut outside, &mut outside_density)
+        };
+        counts.cells += 1;
+        counts.ones += bit as u64;
+        densities.push(local_density(&layer, (x, y)));
+    }
+
+    // Pattern-match scores of the sites and of random windows away from them
+    let mut rng = StdRng::seed_from_u64(game_state.get_seed() ^ 0xA5A5_5A5A_A5A5_5A5A);
+    let mut sites = Vec::new();
+    let mut site_scores = Vec::new();
+    let mut null_scores = Vec::new();
+    let layer_sites = game_state
+        .sites
+        .get_all_sites()
+        .iter()
+        .filter(|site| site.is_flat_on_layer(z, &game_state.bmp));
+    for site in layer_sites {
+        let bitmap = site
+            .get_pattern(&game_state.bmp)
+            .index_axis(Axis(2), 0)
+            .to_owned();
+        let bits = bitmap.len() as u64;
+        let ones = bitmap.iter().filter(|&&b| b).count() as u64;
+        let Some(matches) =
+            matching_bits_on_layer(&site.position, z, &game_state.state, &bitmap, wrap)
+        else {
+            continue;
+        };
+
+        sites.push(SiteAnalysis {
+            position: site.position,
+            is_active: site.is_active,
+            matches: matches as u64,
+            bits,
+            p_value: pattern_match_p_value(matches as u64, ones, bits - ones, fill_ratio),
+        });
+        site_scores.push(matches as f64 / bits as f64);
+
+        for (position, z) in
+            reference_windows(game_state, &windows, bitmap.dim(), null_windows, &mut rng)
+        {
+            if let Some(m) = matching_bits_on_layer(&position, z, &game_state.state, &bitmap, wrap)
+            {
+                null_scores.push(m as f64 / bits as f64);
+            }
+        }
+    }
+
+    Ok(AnalysisReport {
+        layer: z,
+        fill_ratio,
+        inside,
+        outside,
+        inside_binomial: binomial_test(inside.ones, inside.cells, fill_ratio),
+        outside_binomial: binomial_test(outside.ones, outside.cells, fill_ratio),
+        bit_frequency_chi_square: chi_square_2x2([
+            [inside.ones, inside.cells - inside.ones],
+            [outside.ones, outside.cells - outside.ones],
+        ]),
+        local_density_ks: ks_two_sample(&inside_density, &outside_density),
+        pattern_match_ks: ks_two_sample(&site_scores, &null_scores),
+        sites,
+    })
+}
+
+// (position, shape) of every flat site on the site layer
+type Window = (Point2, (usize, usize));
+
+fn site_windows(game_state: &GameState) -> Vec<Window> {
+    game_state
+        .sites
+        .get_all_sites()
+        .iter()
+        .filter(|site| site.is_flat_on_layer(game_state.config.site_layer, &game_state.bmp))
+        .map(|site| (site.position, site.get_dimensions(&game_state.bmp)))
+        .collect()
+}
+
+// Fraction of true bits among the in-plane neighbours of `point`
+fn local_density(layer: &ArrayView2<bool>, point: Point2) -> f64 {
+    let (rows, cols) = layer.dim();
+    let mut total = 0;
+    let mut ones = 0;
+    for x in point.0.saturating_sub(1)..(point.0 + 2).min(rows) {
+        for y in point.1.saturating_sub(1)..(point.1 + 2).min(cols) {
+            if (x, y) != point {
+                total += 1;
+                ones += layer[[x, y]] as usize;
+            }
+        }
+    }
+    ones as f64 / total as f64
+}
+
+// Up to `count` (position, layer) windows of size `dim` that no site guides
+fn reference_windows(
+    game_state: &GameState,
+    sites: &[Window],
+    dim: (usize, usize),
+    count: usize,
+    rng: &mut StdRng,
+) -> Vec<(Point2, usize)> {
+    let (rows, cols, depth) = game_state.state.dim();
+    if dim.0 > rows || dim.1 > cols {
+        return Vec::new();
+    }
+    let wrap = game_state.config.wrap();
+    // Windows may start anywhere along an axis that wraps
+    let last_start =
+        |len: usize, extent: usize, wrap: bool| if wrap { len - 1 } else { len - extent };
+    let random_position = |rng: &mut StdRng| {
+        (
+            rng.gen_range(0..=last_start(rows, dim.0, wrap.0)),
+            rng.gen_range(0..=last_start(cols, dim.1, wrap.1)),
+        )
+    };
+
+    let site_layer = game_state.config.site_layer;
+    let unguided_layers: Vec<usize> = (0..depth)
+        .filter(|&z| {
+            z != site_layer
+                && !game_state.sites.get_all_sites().iter().any(|site| {
+                    let layers = site.get_extent(&game_state.bmp).2;
+                    (site.z..site.z + layers).contains(&z)
+                })
+        })
+        .collect();
+    if !unguided_layers.is_empty() {
+        return (0..count)
+            .map(|_| {
+                let position = random_position(rng);
+                (
+                    position,
+                    unguided_layers[rng.gen_range(0..unguided_layers.len())],
+                )
+            })
+            .collect();
+    }
+
+    let mut windows = Vec::new();
+    // Give up eventually if the sites leave too little free space
+    for _ in 0..count * 20 {
+        if windows.len() == count {
+            break;
+        }
+        let position = random_position(rng);
+        let overlaps_site = sites
+            .iter()
+            .any(|(site, shape)| windows_overlap(&position, dim, site, *shape, (rows, cols), wrap));
+        if !overlaps_site {
+            windows.push((position, site_layer));
+        }
+    }
+    windows
+}
+
+impl fmt::Display for AnalysisReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let test = |f: &mut fmt::Formatter<'_>, name: &str, t: &TestResult| {
+            writeln!(
+                f,
+                "  {:<34} statistic {:>10.4}   p = {:.3e}",
+                name, t.statistic, t.p_value
+            )
+        };
+
+        writeln!(f, "site layer: {}", self.layer)?;
+        writeln!(f, "null model fill ratio: {:.4}", self.fill_ratio)?;
+        writeln!(
+            f,
+            "inside sites:  {:>6} cells, {:>6} ones",
+            self.inside.cells, self.inside.ones
+        )?;
+        writeln!(
+            f,
+            "outside sites: {:>6} cells, {:>6} ones",
+            self.outside.cells, self.outside.ones
+        )?;
+        test(f, "binomial, inside sites", &self.inside_binomial)?;
+        test(f, "binomial, outside sites", &self.outside_binomial)?;
+        test(
+            f,
+            "chi-square, bit frequency",
+            &self.bit_frequency_chi_square,
+        )?;
+        test(f, "KS, local density in vs out", &self.local_density_ks)?;
+        test(
+            f,
+            "KS, pattern match sites vs random",
+            &self.pattern_match_ks,
+        )?;
+        for site in &self.sites {
+            writeln!(
+                f,
+                "  site at ({:>3}, {:>3}) {:<9} {:>5}/{:<5} bits match   p = {:.3e}",
+                site.position.0,
+                site.position.1,
+                if site.is_active {
+                    "active"
+                } else {
+                    "completed"
+                },
+                site.matches,
+                site.bits,
+                site.p_value
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{GridSize, SimConfig};
+
+    // A 20x20x2 run for `bmp` with one site at (4, 4) on `layer`
+    fn game_state_with_site(bmp: Array2<bool>, paint_pattern: bool, layer: usize) -> GameState {
+        let config = SimConfig {
+            grid_size: GridSize { x: 20, y: 20, z: 2 },
+            n_sites: 0,
+            site_layer: layer,
+            ..SimConfig::default()
+        };
+        let mut game_state = GameState::from_bitmaps(config, bmp.clone(), bmp.clone(), 11).unwrap();
+        game_state.sites.add_site((4, 4)).z = layer;
+        if paint_pattern {
+            for ((i, j), &bit) in bmp.indexed_iter() {
+                game_state.state[[4 + i, 4 + j, layer]] = bit;
+            }
+        }
+        game_state
+    }
+
+    fn checkerboard(n: usize) -> Array2<bool> {
+        Array2::from_shape_fn((n, n), |(i, j)| (i + j) % 2 == 0)
+    }
+
+    #[test]
+    fn test_formed_pattern_is_significant() {
+        let report = analyze(&game_state_with_site(checkerboard(8), true, 0), 50).unwrap();
+        assert_eq!(report.inside.cells, 64);
+        assert_eq!(report.inside.cells + report.outside.cells, 400);
+        assert_eq!(report.sites.len(), 1);
+        assert_eq!(report.sites[0].matches, 64);
+        assert!(report.sites[0].p_value < 1e-12);
+        // The one site beats every random window
+        assert_eq!(report.pattern_match_ks.statistic, 1.0);
+    }
+
+    #[test]
+    fn test_random_site_is_not_significant() {
+        let report = analyze(&game_state_with_site(checkerboard(8), false, 0), 50).unwrap();
+        assert!(report.sites[0].p_value > 0.001);
+    }
+
+    #[test]
+    fn test_report_is_reproducible() {
+        let game_state = game_state_with_site(checkerboard(4), false, 0);
+        assert_eq!(analyze(&game_state, 20), analyze(&game_state, 20));
+    }
+
+    #[test]
+    fn test_analyzes_the_site_layer() {
+        let mut game_state = game_state_with_site(checkerboard(8), true, 1);
+        let report = analyze(&game_state, 50).unwrap();
+        assert_eq!(report.layer, 1);
+        assert_eq!(report.inside.cells, 64);
+        assert_eq!(report.sites[0].matches, 64);
+        assert!(report.sites[0].p_value < 1e-12);
+        // The reference windows come from the unguided layer 0
+        assert_eq!(report.pattern_match_ks.statistic, 1.0);
+
+        // Layer 0 holds no site, so there is nothing to analyse there
+        game_state.config.site_layer = 0;
+        assert!(analyze(&game_state, 50).is_err());
+    }
+}
@@ -0,0 +1,146 @@
+use crate::state::{Completion, GameState};
+use serde::Serialize;
+use std::fmt;
+
+/// When a headless run stops
+///
+/// With neither limit set the run never ends.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BatchOptions {
+    /// Stop after this many steps in total
+    pub max_steps: Option<usize>,
+    /// Stop once this many patterns have completed
+    pub target_completions: Option<usize>,
+}
+
+impl BatchOptions {
+    /// Which limit `game_state` has reached, if any
+    pub fn stop_reason(&self, game_state: &GameState) -> Option<StopReason> {
+        if let Some(target) = self.target_completions {
+            if game_state.get_completions().len() >= target {
+                return Some(StopReason::TargetCompletions);
+            }
+        }
+        if let Some(max_steps) = self.max_steps {
+            if game_state.get_step_count() >= max_steps {
+                return Some(StopReason::StepLimit);
+            }
+        }
+        None
+    }
+}
+
+/// Why a headless run stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    StepLimit,
+    TargetCompletions,
+}
+
+/// Outcome of a headless run
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchSummary {
+    pub seed: u64,
+    pub steps: usize,
+    pub stop_reason: StopReason,
+    pub completions: Vec<Completion>,
+    pub active_sites: usize,
+    pub total_sites: usize,
+    /// Fraction of true bits in the whole grid
+    pub fill_ratio: f64,
+    /// Fraction of true bits on the z=0 display layer
+    pub display_fill_ratio: f64,
+    /// Metropolis temperature at the end of the run
+    pub temperature: f64,
+}
+
+impl BatchSummary {
+    /// Summarise `game_state` as it is now
+    pub fn from_state(game_state: &GameState, stop_reason: StopReason) -> Self {
+        Self {
+            seed: game_state.get_seed(),
+            steps: game_state.get_step_count(),
+            stop_reason,
+            completions: game_state.get_completions().to_vec(),
+            active_sites: game_state.sites.active_count(),
+            total_sites: game_state.sites.total_count(),
+            fill_ratio: game_state.fill_ratio(),
+            display_fill_ratio: game_state.layer_fill_ratio(0),
+            temperature: game_state.get_temperature(),
+        }
+    }
+}
+
+impl fmt::Display for BatchSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stop_reason = match self.stop_reason {
+            StopReason::StepLimit => "step limit",
+            StopReason::TargetCompletions => "target completions",
+        };
+        writeln!(f, "seed:               {}", self.seed)?;
+        writeln!(f, "steps:              {} ({})", self.steps, stop_reason)?;
+        writeln!(f, "completions:        {}", self.completions.len())?;
+        writeln!(
+            f,
+            "sites:              {} active / {} total",
+            self.active_sites, self.total_sites
+        )?;
+        writeln!(f, "fill ratio:         {:.4}", self.fill_ratio)?;
+        writeln!(f, "display fill ratio: {:.4}", self.display_fill_ratio)?;
+        writeln!(f, "temperature:        {:.6}", self.temperature)?;
+        for completion in &self.completions {
+            writeln!(
+                f,
+                "  site at ({:>3}, {:>3}) completed at step {} after {} steps",
+                completion.position.0, completion.position.1, completion.step, completion.age
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Step `game_state` until one of the limits in `options` is reached
+pub fn run_batch(game_state: &mut GameState, options: &BatchOptions) -> BatchSummary {
+    loop {
+        if let Some(stop_reason) = options.stop_reason(game_state) {
+            return BatchSummary::from_state(game_state, stop_reason);
+        }
+        game_state.step();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::small_game_state;
+
+    #[test]
+    fn test_stops_at_step_limit() {
+        let mut game_state = small_game_state(7, |_| {});
+        let options = BatchOptions {
+            max_steps: Some(500),
+            target_completions: None,
+        };
+        let summary = run_batch(&mut game_state, &options);
+        assert_eq!(summary.steps, 500);
+        assert_eq!(summary.stop_reason, StopReason::StepLimit);
+        assert_eq!(summary.seed, 7);
+    }
+
+    #[test]
+    fn test_stops_at_target_completions() {
+        let mut game_state = small_game_state(7, |_| {});
+        let options = BatchOptions {
+            max_steps: Some(1_000_000),
+            target_completions: Some(3),
+        };
+        let summary = run_batch(&mut game_state, &options);
+        assert_eq!(summary.stop_reason, StopReason::TargetCompletions);
+        assert_eq!(summary.completions.len(), 3);
+        for completion in &summary.completions {
+            assert!(completion.step <= summary.steps);
+            assert!(completion.age <= completion.step);
+        }
+    }
+}
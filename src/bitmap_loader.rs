@@ -36,21 +36,45 @@ pub fn bitmap_from_image(img: &DynamicImage) -> Result<Array2<bool>, Box<dyn std
     Ok(bitmap)
 }
 
+/// A bitmap with the name it is listed under in the TUI palette
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamedBitmap {
+    pub name: String,
+    pub bitmap: Array2<bool>,
+}
+
 /// Load multiple bitmaps from a directory
 pub fn load_bitmaps_from_directory<P: AsRef<Path>>(
     dir_path: P,
 ) -> Result<Vec<Array2<bool>>, Box<dyn std::error::Error>> {
-    let mut bitmaps = Vec::new();
+    let named = load_named_bitmaps_from_directory(dir_path)?;
+    Ok(named.into_iter().map(|named| named.bitmap).collect())
+}
 
+/// Load every image in a directory, named after its file stem and sorted by
+/// file name
+pub fn load_named_bitmaps_from_directory<P: AsRef<Path>>(
+    dir_path: P,
+) -> Result<Vec<NamedBitmap>, Box<dyn std::error::Error>> {
+    let mut paths = Vec::new();
     for entry in fs::read_dir(dir_path)? {
-        let entry = entry?;
-        let path = entry.path();
+        paths.push(entry?.path());
+    }
+    paths.sort();
 
+    let mut bitmaps = Vec::new();
+    for path in paths {
         if let Some(extension) = path.extension() {
             if extension == "bmp" || extension == "png" || extension == "jpg" || extension == "jpeg"
             {
                 match load_bitmap_from_bmp(&path) {
-                    Ok(bitmap) => bitmaps.push(bitmap),
+                    Ok(bitmap) => bitmaps.push(NamedBitmap {
+                        name: path
+                            .file_stem()
+                            .map(|stem| stem.to_string_lossy().into_owned())
+                            .unwrap_or_default(),
+                        bitmap,
+                    }),
                     Err(e) => eprintln!("Failed to load bitmap from {:?}: {}", path, e),
                 }
             }
@@ -90,6 +114,65 @@ pub fn save_bitmap_as_bmp<P: AsRef<Path>>(
     Ok(())
 }
 
+/// Built-in bitmaps for player sites
+pub fn create_custom_bitmaps() -> Vec<Array2<bool>> {
+    builtin_bitmaps()
+        .into_iter()
+        .map(|named| named.bitmap)
+        .collect()
+}
+
+/// Built-in bitmaps for player sites, with their palette names
+pub fn builtin_bitmaps() -> Vec<NamedBitmap> {
+    let named = |name: &str, bitmap| NamedBitmap {
+        name: name.to_string(),
+        bitmap,
+    };
+    vec![
+        // Simple cross pattern
+        named(
+            "cross",
+            array![
+                [false, true, false],
+                [true, true, true],
+                [false, true, false],
+            ],
+        ),
+        // Small square
+        named("small_square", array![[true, true], [true, true],]),
+        // Diagonal line
+        named(
+            "small_diagonal",
+            array![
+                [true, false, false],
+                [false, true, false],
+                [false, false, true],
+            ],
+        ),
+        // Hollow square
+        named(
+            "small_hollow_square",
+            array![[true, true, true], [true, false, true], [true, true, true],],
+        ),
+    ]
+}
+
+/// The palette of player bitmaps: every image in `dir_path` (if it exists)
+/// followed by the built-in bitmaps
+pub fn load_bitmap_library<P: AsRef<Path>>(dir_path: P) -> Vec<NamedBitmap> {
+    let mut bitmaps = load_named_bitmaps_from_directory(dir_path).unwrap_or_default();
+    bitmaps.extend(builtin_bitmaps());
+    bitmaps
+}
+
+/// Load bitmaps from the "bitmaps" directory followed by the built-in ones
+pub fn load_bitmaps_from_files() -> Vec<Array2<bool>> {
+    load_bitmap_library("bitmaps")
+        .into_iter()
+        .map(|named| named.bitmap)
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,292 @@
+use anscombe::config::{
+    Acceptance, Boundary, Dynamics, GridSize, Neighborhood, Schedule, SimConfig,
+};
+use clap::{Parser, ValueEnum};
+use std::path::{Path, PathBuf};
+
+/// Which frontend drives the simulation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Frontend {
+    /// Interactive cursive view of the display layer
+    Tui,
+    /// Run without a terminal UI and print the outcome
+    Headless,
+    /// Run many independently seeded headless replicas and aggregate them
+    Experiment,
+    /// Run with and without site guidance from the same seed and compare them
+    Compare,
+}
+
+/// Simulate G. E. M. Anscombe's box of bits forming patterns
+#[derive(Debug, Parser)]
+#[command(name = "anscombe", version)]
+pub struct Cli {
+    /// Target pattern every automatically placed site tries to form
+    #[arg(long, value_name = "PATH", default_value = "main_bitmap.bmp")]
+    pub main_bitmap: PathBuf,
+
+    /// Pattern placed by the player when forcing a site
+    #[arg(long, value_name = "PATH", default_value = "player_bitmap.bmp")]
+    pub player_bitmap: PathBuf,
+
+    /// Directory that relative bitmap paths are resolved against
+    #[arg(long, value_name = "DIR", default_value = ".")]
+    pub bitmap_dir: PathBuf,
+
+    /// Directory of extra player bitmaps offered in the TUI palette, and where
+    /// the bitmap editor saves new ones
+    #[arg(long, value_name = "DIR", default_value = "bitmaps")]
+    pub palette_dir: PathBuf,
+
+    /// Seed for every random draw; a fresh one is picked and printed if omitted
+    #[arg(long, env = "ANSCOMBE_SEED")]
+    pub seed: Option<u64>,
+
+    /// TOML or JSON file with simulation parameters
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Override a configuration value, e.g. --set n_trials=200 (repeatable)
+    #[arg(long = "set", value_name = "KEY=VALUE")]
+    pub overrides: Vec<String>,
+
+    /// Grid extent: an edge length for a cube, or XxYxZ (e.g. 120x80x1 for a flat box)
+    #[arg(long, value_name = "N|XxYxZ")]
+    pub grid_size: Option<GridSize>,
+
+    /// Number of sites placed at startup
+    #[arg(long)]
+    pub sites: Option<usize>,
+
+    /// Probability that an exchange happens regardless of any site
+    #[arg(long)]
+    pub probability_anyway: Option<f64>,
+
+    /// Probability of an exchange away from sites
+    #[arg(long)]
+    pub probability_exchange: Option<f64>,
+
+    /// Boundary of every axis: wall, periodic or reflecting (use --set boundary_x=... per axis)
+    #[arg(long)]
+    pub boundary: Option<Boundary>,
+
+    /// Move proposed each step: kawasaki (swap neighbours) or glauber (flip one cell)
+    #[arg(long)]
+    pub dynamics: Option<Dynamics>,
+
+    /// Swap partners: von_neumann, moore, plane4, plane8 or long_range
+    #[arg(long)]
+    pub neighborhood: Option<Neighborhood>,
+
+    /// Rule for keeping moves that touch a site: greedy or metropolis
+    #[arg(long)]
+    pub acceptance: Option<Acceptance>,
+
+    /// Temperature of the metropolis acceptance rule
+    #[arg(long)]
+    pub temperature: Option<f64>,
+
+    /// Cooling schedule of the temperature: constant, linear, exponential or logarithmic
+    #[arg(long)]
+    pub schedule: Option<Schedule>,
+
+    /// Restart the cooling schedule whenever a site completes
+    #[arg(long)]
+    pub reheat: bool,
+
+    /// Exchange bits without regard to sites (the exchange-only null model)
+    #[arg(long)]
+    pub no_site_guidance: bool,
+
+    /// Stop simulating after this many steps
+    #[arg(long)]
+    pub steps: Option<usize>,
+
+    /// Headless and compare only: stop once this many patterns have completed
+    #[arg(long)]
+    pub completions: Option<usize>,
+
+    /// Headless and compare only: print the report as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    /// Headless only: test the final display layer against the exchange-only null model
+    #[arg(long)]
+    pub analyze: bool,
+
+    /// Compare only: record the divergence every this many steps
+    #[arg(long, default_value_t = 100_000)]
+    pub checkpoint_interval: usize,
+
+    /// Experiment only: number of replicas, seeded from --seed
+    #[arg(long, default_value_t = 100)]
+    pub replicas: usize,
+
+    /// Experiment only: write the aggregate table to this file as well as stdout
+    #[arg(long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Experiment only: write one line per replica to this file
+    #[arg(long, value_name = "FILE")]
+    pub replica_output: Option<PathBuf>,
+
+    /// Frontend to run (defaults to tui when built with the `tui` feature)
+    #[arg(long, value_enum)]
+    pub frontend: Option<Frontend>,
+}
+
+impl Cli {
+    /// Configuration from the config file, then `--set` overrides, then dedicated flags
+    pub fn build_config(&self) -> Result<SimConfig, Box<dyn std::error::Error>> {
+        let mut config = match &self.config {
+            Some(path) => SimConfig::from_file(path)?,
+            None => SimConfig::default(),
+        };
+
+        for assignment in &self.overrides {
+            config.set_override(assignment)?;
+        }
+
+        if let Some(grid_size) = self.grid_size {
+            config.grid_size = grid_size;
+        }
+        if let Some(sites) = self.sites {
+            config.n_sites = sites;
+        }
+        if let Some(probability) = self.probability_anyway {
+            config.probability_anyway = probability;
+        }
+        if let Some(probability) = self.probability_exchange {
+            config.probability_exchange = probability;
+        }
+        if let Some(boundary) = self.boundary {
+            config.boundary_x = boundary;
+            config.boundary_y = boundary;
+            config.boundary_z = boundary;
+        }
+        if let Some(dynamics) = self.dynamics {
+            config.dynamics = dynamics;
+        }
+        if let Some(neighborhood) = self.neighborhood {
+            config.neighborhood = neighborhood;
+        }
+        if let Some(acceptance) = self.acceptance {
+            config.acceptance = acceptance;
+        }
+        if let Some(temperature) = self.temperature {
+            config.temperature = temperature;
+        }
+        if let Some(schedule) = self.schedule {
+            config.schedule = schedule;
+        }
+        if self.reheat {
+            config.reheat = true;
+        }
+        if self.no_site_guidance {
+            config.site_guidance = false;
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Resolve a bitmap path against `--bitmap-dir`
+    pub fn bitmap_path(&self, path: &Path) -> PathBuf {
+        self.bitmap_dir.join(path)
+    }
+
+    pub fn frontend(&self) -> Frontend {
+        self.frontend.unwrap_or(if cfg!(feature = "tui") {
+            Frontend::Tui
+        } else {
+            Frontend::Headless
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flags_override_set_and_defaults() {
+        let cli = Cli::try_parse_from([
+            "anscombe",
+            "--set",
+            "n_sites=2",
+            "--set",
+            "grid_size=40",
+            "--grid-size",
+            "30",
+        ])
+        .unwrap();
+        let config = cli.build_config().unwrap();
+        assert_eq!(config.n_sites, 2);
+        assert_eq!(config.grid_size, GridSize::cube(30));
+        assert_eq!(config.n_trials, SimConfig::default().n_trials);
+    }
+
+    #[test]
+    fn test_non_cubic_grid_size() {
+        let cli = Cli::try_parse_from(["anscombe", "--grid-size", "80x40x1"]).unwrap();
+        let config = cli.build_config().unwrap();
+        assert_eq!(config.grid_size, GridSize { x: 80, y: 40, z: 1 });
+    }
+
+    #[test]
+    fn test_invalid_probability_is_an_error() {
+        let cli = Cli::try_parse_from(["anscombe", "--probability-exchange", "1.5"]).unwrap();
+        assert!(cli.build_config().is_err());
+    }
+
+    #[test]
+    fn test_no_site_guidance_flag() {
+        let cli = Cli::try_parse_from(["anscombe", "--set", "site_guidance=true"]).unwrap();
+        assert!(cli.build_config().unwrap().site_guidance);
+        let cli = Cli::try_parse_from(["anscombe", "--no-site-guidance"]).unwrap();
+        assert!(!cli.build_config().unwrap().site_guidance);
+    }
+
+    #[test]
+    fn test_acceptance_flags() {
+        let cli = Cli::try_parse_from([
+            "anscombe",
+            "--acceptance",
+            "metropolis",
+            "--temperature",
+            "0.3",
+            "--schedule",
+            "logarithmic",
+            "--reheat",
+            "--dynamics",
+            "glauber",
+            "--neighborhood",
+            "long_range",
+            "--boundary",
+            "periodic",
+        ])
+        .unwrap();
+        let config = cli.build_config().unwrap();
+        assert_eq!(config.acceptance, Acceptance::Metropolis);
+        assert_eq!(config.temperature, 0.3);
+        assert_eq!(config.schedule, Schedule::Logarithmic);
+        assert!(config.reheat);
+        assert_eq!(config.dynamics, Dynamics::Glauber);
+        assert_eq!(config.neighborhood, Neighborhood::LongRange);
+        assert_eq!(config.boundaries(), [Boundary::Periodic; 3]);
+        assert!(Cli::try_parse_from(["anscombe", "--acceptance", "annealing"]).is_err());
+    }
+
+    #[test]
+    fn test_bitmap_paths_resolve_against_bitmap_dir() {
+        let cli = Cli::try_parse_from(["anscombe", "--bitmap-dir", "assets"]).unwrap();
+        assert_eq!(
+            cli.bitmap_path(&cli.main_bitmap),
+            PathBuf::from("assets/main_bitmap.bmp")
+        );
+        assert_eq!(
+            cli.bitmap_path(&cli.palette_dir),
+            PathBuf::from("assets/bitmaps")
+        );
+    }
+}
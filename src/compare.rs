@@ -0,0 +1,272 @@
+use crate::batch::{BatchOptions, StopReason};
+use crate::config::ConfigError;
+use crate::goodness::goodness_3d;
+use crate::state::{GameState, Point2};
+use ndarray::*;
+use serde::Serialize;
+use std::fmt;
+
+/// Goodness of one site in the guided run and, at the same place, in the control run
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SiteComparison {
+    pub position: Point2,
+    pub z: usize,
+    /// Whether the site is still active in the guided run
+    pub is_active: bool,
+    pub guided_goodness: f32,
+    pub control_goodness: f32,
+}
+
+/// Divergence between the guided and control runs at one step
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Checkpoint {
+    pub step: usize,
+    pub guided_completions: usize,
+    /// Mean goodness over every guided-run site, in each grid
+    pub guided_mean_goodness: f32,
+    pub control_mean_goodness: f32,
+    /// Fraction of display-layer cells that differ between the runs
+    pub display_divergence: f64,
+    /// Fraction of all cells that differ between the runs
+    pub grid_divergence: f64,
+}
+
+/// Side-by-side outcome of a guided run and its unguided control
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ComparisonReport {
+    pub seed: u64,
+    pub stop_reason: StopReason,
+    pub checkpoints: Vec<Checkpoint>,
+    pub sites: Vec<SiteComparison>,
+    pub guided_fill_ratio: f64,
+    pub control_fill_ratio: f64,
+    pub guided_display_fill_ratio: f64,
+    pub control_display_fill_ratio: f64,
+}
+
+/// Run `game_state` with site guidance next to a copy of it without
+///
+/// Both runs start from the same grid, sites and seed, and take one step each
+/// at a time until the guided run reaches a limit in `options`. Each run draws
+/// from its own copy of the random stream; a move involving a site takes
+/// different draws than an unguided one, so the streams part ways at the
+/// guided run's first site-guided decision. A checkpoint is recorded every
+/// `checkpoint_interval` steps and at the end.
+///
+/// Fails if `checkpoint_interval` is 0, or if `game_state` has site guidance
+/// turned off, since the guided run would then be a second control.
+pub fn run_comparison(
+    game_state: GameState,
+    options: &BatchOptions,
+    checkpoint_interval: usize,
+) -> Result<ComparisonReport, ConfigError> {
+    if checkpoint_interval == 0 {
+        return Err(ConfigError(
+            "the checkpoint interval must be at least 1".to_string(),
+        ));
+    }
+    if !game_state.config.site_guidance {
+        return Err(ConfigError(
+            "the compare frontend needs site guidance for its guided run".to_string(),
+        ));
+    }
+    let mut guided = game_state;
+    let mut control = guided.clone();
+    control.config.site_guidance = false;
+
+    let mut checkpoints = vec![checkpoint(&guided, &control)];
+    let stop_reason = loop {
+        if let Some(stop_reason) = options.stop_reason(&guided) {
+            break stop_reason;
+        }
+        guided.step();
+        control.step();
+        if guided.get_step_count() % checkpoint_interval == 0 {
+            checkpoints.push(checkpoint(&guided, &control));
+        }
+    };
+    if checkpoints.last().map(|c| c.step) != Some(guided.get_step_count()) {
+        checkpoints.push(checkpoint(&guided, &control));
+    }
+
+    let sites = guided
+        .sites
+        .get_all_sites()
+        .iter()
+        .map(|site| {
+            let pattern = site.get_pattern(&guided.bmp);
+            let wrap = guided.config.wrap();
+            SiteComparison {
+                position: site.position,
+                z: site.z,
+                is_active: site.is_active,
+                guided_goodness: goodness_3d(&site.origin(), &guided.state, pattern, wrap),
+                control_goodness: goodness_3d(&site.origin(), &control.state, pattern, wrap),
+            }
+        })
+        .collect();
+
+    Ok(ComparisonReport {
+        seed: guided.get_seed(),
+        stop_reason,
+        checkpoints,
+        sites,
+        guided_fill_ratio: guided.fill_ratio(),
+        control_fill_ratio: control.fill_ratio(),
+        guided_display_fill_ratio: guided.layer_fill_ratio(0),
+        control_display_fill_ratio: control.layer_fill_ratio(0),
+    })
+}
+
+fn checkpoint(guided: &GameState, control: &GameState) -> Checkpoint {
+    let sites = guided.sites.get_all_sites();
+    let mean_goodness = |state: &Array3<bool>| {
+        if sites.is_empty() {
+            return 0.0;
+        }
+        let total: f32 = sites
+            .iter()
+            .map(|site| {
+                let pattern = site.get_pattern(&guided.bmp);
+                goodness_3d(&site.origin(), state, pattern, guided.config.wrap())
+            })
+            .sum();
+        total / sites.len() as f32
+    };
+
+    Checkpoint {
+        step: guided.get_step_count(),
+        guided_completions: guided.get_completions().len(),
+        guided_mean_goodness: mean_goodness(&guided.state),
+        control_mean_goodness: mean_goodness(&control.state),
+        display_divergence: divergence(
+            guided.state.slice(s![.., .., 0]),
+            control.state.slice(s![.., .., 0]),
+        ),
+        grid_divergence: divergence(guided.state.view(), control.state.view()),
+    }
+}
+
+// Fraction of cells that differ between two equally shaped grids
+fn divergence<D: Dimension>(a: ArrayView<bool, D>, b: ArrayView<bool, D>) -> f64 {
+    let differing = Zip::from(&a)
+        .and(&b)
+        .fold(0usize, |n, &x, &y| n + (x != y) as usize);
+    differing as f64 / a.len() as f64
+}
+
+impl fmt::Display for ComparisonReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "seed: {}", self.seed)?;
+        writeln!(
+            f,
+            "{:>12} {:>11} {:>14} {:>15} {:>18} {:>15}",
+            "step",
+            "completions",
+            "guided good.",
+            "control good.",
+            "display diverged",
+            "grid diverged"
+        )?;
+        for c in &self.checkpoints {
+            writeln!(
+                f,
+                "{:>12} {:>11} {:>14.4} {:>15.4} {:>18.4} {:>15.4}",
+                c.step,
+                c.guided_completions,
+                c.guided_mean_goodness,
+                c.control_mean_goodness,
+                c.display_divergence,
+                c.grid_divergence
+            )?;
+        }
+
+        writeln!(
+            f,
+            "{:>12} {:>10} {:>14} {:>15}",
+            "site", "status", "guided", "control"
+        )?;
+        for site in &self.sites {
+            writeln!(
+                f,
+                "{:>12} {:>10} {:>14.4} {:>15.4}",
+                format!("({}, {}, {})", site.position.0, site.position.1, site.z),
+                if site.is_active {
+                    "active"
+                } else {
+                    "completed"
+                },
+                site.guided_goodness,
+                site.control_goodness
+            )?;
+        }
+
+        writeln!(
+            f,
+            "fill ratio:         guided {:.4}, control {:.4}",
+            self.guided_fill_ratio, self.control_fill_ratio
+        )?;
+        writeln!(
+            f,
+            "display fill ratio: guided {:.4}, control {:.4}",
+            self.guided_display_fill_ratio, self.control_display_fill_ratio
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::small_game_state;
+
+    #[test]
+    fn test_runs_start_identical_and_stay_in_lockstep() {
+        let options = BatchOptions {
+            max_steps: Some(5_000),
+            target_completions: None,
+        };
+        let report = run_comparison(small_game_state(9, |_| {}), &options, 1_000).unwrap();
+
+        let first = &report.checkpoints[0];
+        assert_eq!(first.step, 0);
+        assert_eq!(first.grid_divergence, 0.0);
+        assert_eq!(first.guided_mean_goodness, first.control_mean_goodness);
+
+        let steps: Vec<usize> = report.checkpoints.iter().map(|c| c.step).collect();
+        assert_eq!(steps, vec![0, 1_000, 2_000, 3_000, 4_000, 5_000]);
+        assert_eq!(report.stop_reason, StopReason::StepLimit);
+
+        // Swaps conserve the number of true bits in both runs
+        assert_eq!(report.guided_fill_ratio, report.control_fill_ratio);
+    }
+
+    #[test]
+    fn test_control_run_never_completes_a_site() {
+        let mut control = small_game_state(9, |_| {});
+        control.config.site_guidance = false;
+        for _ in 0..20_000 {
+            control.step();
+        }
+        assert!(control.get_completions().is_empty());
+    }
+
+    #[test]
+    fn test_comparison_needs_site_guidance() {
+        let mut game_state = small_game_state(9, |_| {});
+        game_state.config.site_guidance = false;
+        let options = BatchOptions {
+            max_steps: Some(10),
+            target_completions: None,
+        };
+        assert!(run_comparison(game_state, &options, 5).is_err());
+    }
+
+    #[test]
+    fn test_comparison_needs_a_checkpoint_interval() {
+        let options = BatchOptions {
+            max_steps: Some(10),
+            target_completions: None,
+        };
+        assert!(run_comparison(small_game_state(9, |_| {}), &options, 0).is_err());
+    }
+}
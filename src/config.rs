@@ -0,0 +1,814 @@
+use crate::goodness::Wrap;
+use ndarray::Array2;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Error returned when a configuration value is missing, malformed or out of range
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigError(pub String);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid configuration: {}", self.0)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Extent of the grid along x (display rows), y (display columns) and z (depth)
+///
+/// Config files give either a single edge length for a cube or `[x, y, z]`;
+/// on the command line the same is written `60` or `60x60x1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "GridSizeRepr", into = "[usize; 3]")]
+pub struct GridSize {
+    pub x: usize,
+    pub y: usize,
+    pub z: usize,
+}
+
+impl GridSize {
+    /// A cube with edge length `n`
+    pub fn cube(n: usize) -> Self {
+        Self { x: n, y: n, z: n }
+    }
+
+    /// Extent of the z=0 display layer as (rows, cols)
+    pub fn layer(&self) -> (usize, usize) {
+        (self.x, self.y)
+    }
+
+    /// Shape of the grid array
+    pub fn dim(&self) -> (usize, usize, usize) {
+        (self.x, self.y, self.z)
+    }
+
+    /// Number of cells in the grid
+    pub fn cells(&self) -> usize {
+        self.x * self.y * self.z
+    }
+}
+
+impl fmt::Display for GridSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}x{}", self.x, self.y, self.z)
+    }
+}
+
+impl FromStr for GridSize {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |part: &str| {
+            part.trim()
+                .parse()
+                .map_err(|_| ConfigError(format!("cannot parse grid size {:?}", s)))
+        };
+        let parts: Vec<&str> = s.split('x').collect();
+        match parts[..] {
+            [n] => Ok(Self::cube(parse(n)?)),
+            [x, y, z] => Ok(Self {
+                x: parse(x)?,
+                y: parse(y)?,
+                z: parse(z)?,
+            }),
+            _ => Err(ConfigError(format!(
+                "grid size must be N or XxYxZ, got {:?}",
+                s
+            ))),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum GridSizeRepr {
+    Cube(usize),
+    Dims([usize; 3]),
+}
+
+impl From<GridSizeRepr> for GridSize {
+    fn from(repr: GridSizeRepr) -> Self {
+        match repr {
+            GridSizeRepr::Cube(n) => Self::cube(n),
+            GridSizeRepr::Dims([x, y, z]) => Self { x, y, z },
+        }
+    }
+}
+
+impl From<GridSize> for [usize; 3] {
+    fn from(size: GridSize) -> Self {
+        [size.x, size.y, size.z]
+    }
+}
+
+/// Rule deciding whether a move touching a site is kept
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Acceptance {
+    /// Keep a move only if it strictly improves the site's goodness
+    #[default]
+    Greedy,
+    /// Keep a move with probability `min(1, exp(-dE / T))`, where `dE` is the
+    /// change in the number of bits that mismatch the site's pattern
+    Metropolis,
+}
+
+impl FromStr for Acceptance {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "greedy" => Ok(Acceptance::Greedy),
+            "metropolis" => Ok(Acceptance::Metropolis),
+            _ => Err(ConfigError(format!(
+                "unknown acceptance rule {:?} (expected greedy or metropolis)",
+                s
+            ))),
+        }
+    }
+}
+
+/// Kind of move each step proposes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Dynamics {
+    /// Swap a cell with a neighbour, conserving the number of true bits
+    #[default]
+    Kawasaki,
+    /// Flip a single cell, so the number of true bits can drift
+    Glauber,
+}
+
+impl FromStr for Dynamics {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "kawasaki" => Ok(Dynamics::Kawasaki),
+            "glauber" => Ok(Dynamics::Glauber),
+            _ => Err(ConfigError(format!(
+                "unknown dynamics {:?} (expected kawasaki or glauber)",
+                s
+            ))),
+        }
+    }
+}
+
+/// Offset from a cell to one of its neighbours
+pub type Offset = (isize, isize, isize);
+
+const VON_NEUMANN: [Offset; 6] = [
+    (1, 0, 0),
+    (-1, 0, 0),
+    (0, 1, 0),
+    (0, -1, 0),
+    (0, 0, 1),
+    (0, 0, -1),
+];
+const PLANE_4: [Offset; 4] = [(1, 0, 0), (-1, 0, 0), (0, 1, 0), (0, -1, 0)];
+const PLANE_8: [Offset; 8] = [
+    (1, 0, 0),
+    (-1, 0, 0),
+    (0, 1, 0),
+    (0, -1, 0),
+    (1, 1, 0),
+    (1, -1, 0),
+    (-1, 1, 0),
+    (-1, -1, 0),
+];
+const MOORE: [Offset; 26] = moore_offsets();
+
+const fn moore_offsets() -> [Offset; 26] {
+    let mut offsets = [(0, 0, 0); 26];
+    let mut n = 0;
+    let mut i = 0;
+    while i < 27 {
+        // Skip the centre of the 3x3x3 cube
+        if i != 13 {
+            offsets[n] = (i / 9 - 1, i / 3 % 3 - 1, i % 3 - 1);
+            n += 1;
+        }
+        i += 1;
+    }
+    offsets
+}
+
+/// Cells a swap partner is drawn from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Neighborhood {
+    /// The 6 face neighbours
+    #[default]
+    VonNeumann,
+    /// The 26 face, edge and corner neighbours
+    Moore,
+    /// The 4 face neighbours on the same z-layer
+    Plane4,
+    /// The 8 face and corner neighbours on the same z-layer
+    Plane8,
+    /// A cell at Chebyshev distance `d` in any direction, where `d` is drawn
+    /// from `1..=max_jump` with probability proportional to `d^-jump_exponent`
+    LongRange,
+}
+
+impl Neighborhood {
+    /// Offsets of every neighbour, or `None` for long-range jumps, which have no fixed set
+    pub fn offsets(self) -> Option<&'static [Offset]> {
+        match self {
+            Neighborhood::VonNeumann => Some(&VON_NEUMANN),
+            Neighborhood::Moore => Some(&MOORE),
+            Neighborhood::Plane4 => Some(&PLANE_4),
+            Neighborhood::Plane8 => Some(&PLANE_8),
+            Neighborhood::LongRange => None,
+        }
+    }
+}
+
+impl FromStr for Neighborhood {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "von_neumann" => Ok(Neighborhood::VonNeumann),
+            "moore" => Ok(Neighborhood::Moore),
+            "plane4" => Ok(Neighborhood::Plane4),
+            "plane8" => Ok(Neighborhood::Plane8),
+            "long_range" => Ok(Neighborhood::LongRange),
+            _ => Err(ConfigError(format!(
+                "unknown neighborhood {:?} (expected von_neumann, moore, plane4, plane8 or long_range)",
+                s
+            ))),
+        }
+    }
+}
+
+/// What happens at the two ends of one grid axis
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Boundary {
+    /// Nothing lies beyond the edge; moves across it are dropped
+    #[default]
+    Wall,
+    /// The axis wraps around, and sites may wrap across the edge
+    Periodic,
+    /// A move across the edge is mirrored back into the grid
+    Reflecting,
+}
+
+impl Boundary {
+    /// Map coordinate `c` onto an axis of length `len`, or `None` if it lies beyond a wall
+    pub fn resolve(self, c: isize, len: usize) -> Option<usize> {
+        let n = len as isize;
+        if (0..n).contains(&c) {
+            return Some(c as usize);
+        }
+        match self {
+            Boundary::Wall => None,
+            Boundary::Periodic => Some(c.rem_euclid(n) as usize),
+            // Mirror about the edge cells: -1 -> 1 and n -> n - 2
+            Boundary::Reflecting if n == 1 => Some(0),
+            Boundary::Reflecting => {
+                let period = 2 * (n - 1);
+                let m = c.rem_euclid(period);
+                Some(if m < n { m } else { period - m } as usize)
+            }
+        }
+    }
+}
+
+/// Index `offset` cells past `start` on an axis of length `len`, wrapping
+/// around if the axis `wraps`; `None` past the edge otherwise
+pub fn offset_along(start: usize, offset: usize, len: usize, wraps: bool) -> Option<usize> {
+    let i = start + offset;
+    if wraps {
+        Some(i % len)
+    } else {
+        (i < len).then_some(i)
+    }
+}
+
+impl FromStr for Boundary {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wall" => Ok(Boundary::Wall),
+            "periodic" => Ok(Boundary::Periodic),
+            "reflecting" => Ok(Boundary::Reflecting),
+            _ => Err(ConfigError(format!(
+                "unknown boundary {:?} (expected wall, periodic or reflecting)",
+                s
+            ))),
+        }
+    }
+}
+
+/// How the Metropolis temperature changes over a run
+///
+/// Every schedule starts from `SimConfig::temperature` and counts steps from
+/// the start of the run, or from the last reheat.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Schedule {
+    /// Stay at the initial temperature
+    #[default]
+    Constant,
+    /// Fall linearly to `final_temperature` over `cooling_steps` steps, then stay there
+    Linear,
+    /// Multiply the temperature by `cooling_rate` every step
+    Exponential,
+    /// `T0 / (1 + ln(1 + t))`, the slow cooling that anneals to a global optimum
+    Logarithmic,
+}
+
+impl FromStr for Schedule {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "constant" => Ok(Schedule::Constant),
+            "linear" => Ok(Schedule::Linear),
+            "exponential" => Ok(Schedule::Exponential),
+            "logarithmic" => Ok(Schedule::Logarithmic),
+            _ => Err(ConfigError(format!(
+                "unknown schedule {:?} (expected constant, linear, exponential or logarithmic)",
+                s
+            ))),
+        }
+    }
+}
+
+/// Runtime parameters of a simulation
+///
+/// Every field has a default, so a config file only needs to list the values
+/// it changes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SimConfig {
+    /// Extent of the grid along each axis
+    pub grid_size: GridSize,
+    /// Number of sites placed by `init_state`
+    pub n_sites: usize,
+    /// Random positions tried when looking for a new site
+    pub n_trials: usize,
+    /// Layer automatically placed sites form the main bitmap on (0 is the display layer)
+    pub site_layer: usize,
+    /// Steps between TUI redraws (fewer if they do not fit in the frame time)
+    pub display_update_interval: usize,
+    /// Goodness above which a site counts as complete
+    pub pattern_completion_threshold: f32,
+    /// Probability that an exchange happens regardless of any site
+    pub probability_anyway: f64,
+    /// Probability of an exchange away from sites
+    pub probability_exchange: f64,
+    /// Whether sites guide exchanges; without guidance every exchange uses
+    /// `probability_exchange`, which is the null model
+    pub site_guidance: bool,
+    /// Boundary along x, the rows of the display layer
+    pub boundary_x: Boundary,
+    /// Boundary along y, the columns of the display layer
+    pub boundary_y: Boundary,
+    /// Boundary along z, the depth below the display layer
+    pub boundary_z: Boundary,
+    /// Kind of move each step proposes
+    pub dynamics: Dynamics,
+    /// Cells a swap partner is drawn from
+    pub neighborhood: Neighborhood,
+    /// Longest jump of the long-range neighborhood
+    pub max_jump: usize,
+    /// Power-law exponent of the long-range jump distance (0 is uniform)
+    pub jump_exponent: f64,
+    /// How moves touching a site are accepted
+    pub acceptance: Acceptance,
+    /// Initial temperature of the Metropolis rule (ignored by the greedy rule)
+    pub temperature: f64,
+    /// How the temperature cools from `temperature`
+    pub schedule: Schedule,
+    /// Temperature the linear schedule ends at
+    pub final_temperature: f64,
+    /// Steps the linear schedule takes to reach `final_temperature`
+    pub cooling_steps: usize,
+    /// Per-step factor of the exponential schedule
+    pub cooling_rate: f64,
+    /// Restart the schedule whenever a site completes and a new one is placed
+    pub reheat: bool,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            grid_size: GridSize::cube(60),
+            n_sites: 4,
+            n_trials: 100,
+            site_layer: 0,
+            display_update_interval: 11_000,
+            pattern_completion_threshold: 0.98,
+            probability_anyway: 0.01,
+            probability_exchange: 0.8,
+            site_guidance: true,
+            boundary_x: Boundary::Wall,
+            boundary_y: Boundary::Wall,
+            boundary_z: Boundary::Wall,
+            dynamics: Dynamics::Kawasaki,
+            neighborhood: Neighborhood::VonNeumann,
+            max_jump: 8,
+            jump_exponent: 2.0,
+            acceptance: Acceptance::Greedy,
+            temperature: 1.0,
+            schedule: Schedule::Constant,
+            final_temperature: 0.0,
+            cooling_steps: 10_000_000,
+            cooling_rate: 0.999_999,
+            reheat: false,
+        }
+    }
+}
+
+impl SimConfig {
+    /// Load a configuration from a `.toml` or `.json` file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)?;
+
+        let config: Self = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(&contents)?,
+            Some("json") => serde_json::from_str(&contents)?,
+            _ => {
+                return Err(Box::new(ConfigError(format!(
+                    "{:?} is neither a .toml nor a .json file",
+                    path
+                ))))
+            }
+        };
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Override a single field from a `key=value` pair given on the command line
+    pub fn set_override(&mut self, assignment: &str) -> Result<(), ConfigError> {
+        let (key, value) = assignment
+            .split_once('=')
+            .ok_or_else(|| ConfigError(format!("expected key=value, got {:?}", assignment)))?;
+        self.set(key.trim(), value.trim())
+    }
+
+    /// Set a single field by name, parsing `value` into the field's type
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        match key {
+            "grid_size" => self.grid_size = parse_value(key, value)?,
+            "n_sites" => self.n_sites = parse_value(key, value)?,
+            "n_trials" => self.n_trials = parse_value(key, value)?,
+            "site_layer" => self.site_layer = parse_value(key, value)?,
+            "display_update_interval" => self.display_update_interval = parse_value(key, value)?,
+            "pattern_completion_threshold" => {
+                self.pattern_completion_threshold = parse_value(key, value)?
+            }
+            "probability_anyway" => self.probability_anyway = parse_value(key, value)?,
+            "probability_exchange" => self.probability_exchange = parse_value(key, value)?,
+            "site_guidance" => self.site_guidance = parse_value(key, value)?,
+            "boundary_x" => self.boundary_x = parse_value(key, value)?,
+            "boundary_y" => self.boundary_y = parse_value(key, value)?,
+            "boundary_z" => self.boundary_z = parse_value(key, value)?,
+            "boundary" => {
+                let boundary = parse_value(key, value)?;
+                self.boundary_x = boundary;
+                self.boundary_y = boundary;
+                self.boundary_z = boundary;
+            }
+            "dynamics" => self.dynamics = parse_value(key, value)?,
+            "neighborhood" => self.neighborhood = parse_value(key, value)?,
+            "max_jump" => self.max_jump = parse_value(key, value)?,
+            "jump_exponent" => self.jump_exponent = parse_value(key, value)?,
+            "acceptance" => self.acceptance = parse_value(key, value)?,
+            "temperature" => self.temperature = parse_value(key, value)?,
+            "schedule" => self.schedule = parse_value(key, value)?,
+            "final_temperature" => self.final_temperature = parse_value(key, value)?,
+            "cooling_steps" => self.cooling_steps = parse_value(key, value)?,
+            "cooling_rate" => self.cooling_rate = parse_value(key, value)?,
+            "reheat" => self.reheat = parse_value(key, value)?,
+            _ => return Err(ConfigError(format!("unknown key {:?}", key))),
+        }
+        Ok(())
+    }
+
+    /// Check that every value is in range
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let GridSize { x, y, z } = self.grid_size;
+        if x == 0 || y == 0 || z == 0 {
+            return Err(ConfigError(format!(
+                "every grid_size axis must be at least 1, got {}",
+                self.grid_size
+            )));
+        }
+        if self.site_layer >= z {
+            return Err(ConfigError(format!(
+                "site_layer must be below the grid depth {}, got {}",
+                z, self.site_layer
+            )));
+        }
+        if self.n_trials == 0 {
+            return Err(ConfigError("n_trials must be at least 1".to_string()));
+        }
+        if self.display_update_interval == 0 {
+            return Err(ConfigError(
+                "display_update_interval must be at least 1".to_string(),
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.pattern_completion_threshold) {
+            return Err(ConfigError(format!(
+                "pattern_completion_threshold must be in [0, 1], got {}",
+                self.pattern_completion_threshold
+            )));
+        }
+        check_probability("probability_anyway", self.probability_anyway)?;
+        check_probability("probability_exchange", self.probability_exchange)?;
+        if self.max_jump == 0 {
+            return Err(ConfigError("max_jump must be at least 1".to_string()));
+        }
+        if !(self.jump_exponent.is_finite() && self.jump_exponent >= 0.0) {
+            return Err(ConfigError(format!(
+                "jump_exponent must be finite and non-negative, got {}",
+                self.jump_exponent
+            )));
+        }
+        check_temperature("temperature", self.temperature)?;
+        check_temperature("final_temperature", self.final_temperature)?;
+        if self.cooling_steps == 0 {
+            return Err(ConfigError("cooling_steps must be at least 1".to_string()));
+        }
+        if !(self.cooling_rate > 0.0 && self.cooling_rate <= 1.0) {
+            return Err(ConfigError(format!(
+                "cooling_rate must be in (0, 1], got {}",
+                self.cooling_rate
+            )));
+        }
+        Ok(())
+    }
+
+    /// Boundaries of the x, y and z axes
+    pub fn boundaries(&self) -> [Boundary; 3] {
+        [self.boundary_x, self.boundary_y, self.boundary_z]
+    }
+
+    /// Whether site windows wrap around the display layer along x and y
+    pub fn wrap(&self) -> Wrap {
+        (
+            self.boundary_x == Boundary::Periodic,
+            self.boundary_y == Boundary::Periodic,
+        )
+    }
+
+    /// Temperature of the schedule `elapsed` steps after it (re)started
+    pub fn scheduled_temperature(&self, elapsed: usize) -> f64 {
+        self.scheduled_temperature_from(self.temperature, elapsed)
+    }
+
+    /// Temperature of the schedule `elapsed` steps after it (re)started from
+    /// `start` instead of the configured temperature
+    pub fn scheduled_temperature_from(&self, start: f64, elapsed: usize) -> f64 {
+        let t = elapsed as f64;
+        match self.schedule {
+            Schedule::Constant => start,
+            Schedule::Linear => {
+                let progress = (t / self.cooling_steps as f64).min(1.0);
+                start + (self.final_temperature - start) * progress
+            }
+            Schedule::Exponential => start * self.cooling_rate.powf(t),
+            Schedule::Logarithmic => start / (1.0 + t.ln_1p()),
+        }
+    }
+
+    /// Check that `bmp` is non-empty and fits inside the grid
+    pub fn validate_bitmap(&self, name: &str, bmp: &Array2<bool>) -> Result<(), ConfigError> {
+        let (rows, cols) = bmp.dim();
+        if rows == 0 || cols == 0 {
+            return Err(ConfigError(format!("{} is empty", name)));
+        }
+        let (grid_rows, grid_cols) = self.grid_size.layer();
+        if rows > grid_rows || cols > grid_cols {
+            return Err(ConfigError(format!(
+                "{} is {}x{} but the display layer is only {}x{}",
+                name, rows, cols, grid_rows, grid_cols
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, ConfigError> {
+    value
+        .parse()
+        .map_err(|_| ConfigError(format!("cannot parse {:?} for {}", value, key)))
+}
+
+fn check_probability(name: &str, value: f64) -> Result<(), ConfigError> {
+    if (0.0..=1.0).contains(&value) {
+        Ok(())
+    } else {
+        Err(ConfigError(format!(
+            "{} must be in [0, 1], got {}",
+            name, value
+        )))
+    }
+}
+
+fn check_temperature(name: &str, value: f64) -> Result<(), ConfigError> {
+    if value.is_finite() && value >= 0.0 {
+        Ok(())
+    } else {
+        Err(ConfigError(format!(
+            "{} must be finite and non-negative, got {}",
+            name, value
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_is_valid() {
+        assert!(SimConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn test_partial_toml_keeps_defaults() {
+        let config: SimConfig =
+            toml::from_str("grid_size = 30\nprobability_exchange = 0.5").unwrap();
+        assert_eq!(config.grid_size, GridSize::cube(30));
+        assert_eq!(config.probability_exchange, 0.5);
+        assert_eq!(config.n_sites, SimConfig::default().n_sites);
+    }
+
+    #[test]
+    fn test_grid_size_forms() {
+        let config: SimConfig = toml::from_str("grid_size = [40, 30, 1]").unwrap();
+        let flat = GridSize { x: 40, y: 30, z: 1 };
+        assert_eq!(config.grid_size, flat);
+        assert!(config.validate().is_ok());
+        assert_eq!("40x30x1".parse(), Ok(flat));
+        assert_eq!("7".parse(), Ok(GridSize::cube(7)));
+        assert!("40x30".parse::<GridSize>().is_err());
+
+        let json = serde_json::to_string(&config).unwrap();
+        assert!(json.contains(r#""grid_size":[40,30,1]"#));
+        let config: SimConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(config.grid_size, flat);
+
+        let config = SimConfig {
+            grid_size: GridSize { x: 5, y: 0, z: 5 },
+            ..SimConfig::default()
+        };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_json_rejects_unknown_fields() {
+        let result: Result<SimConfig, _> = serde_json::from_str(r#"{"grid_sise": 30}"#);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_set_override() {
+        let mut config = SimConfig::default();
+        config.set_override("n_sites=7").unwrap();
+        assert_eq!(config.n_sites, 7);
+        assert!(config.set_override("n_sites").is_err());
+        assert!(config.set_override("n_sites=lots").is_err());
+        assert!(config.set_override("unknown=1").is_err());
+    }
+
+    #[test]
+    fn test_acceptance_rule() {
+        let config: SimConfig =
+            toml::from_str("acceptance = \"metropolis\"\ntemperature = 0.5").unwrap();
+        assert_eq!(config.acceptance, Acceptance::Metropolis);
+        assert_eq!(config.temperature, 0.5);
+
+        let mut config = SimConfig::default();
+        assert_eq!(config.acceptance, Acceptance::Greedy);
+        config.set_override("acceptance=metropolis").unwrap();
+        config.set_override("dynamics=glauber").unwrap();
+        assert_eq!(config.dynamics, Dynamics::Glauber);
+        assert_eq!(config.acceptance, Acceptance::Metropolis);
+        assert!(config.set_override("acceptance=annealing").is_err());
+
+        config.temperature = -1.0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_boundary_resolve() {
+        for boundary in [Boundary::Wall, Boundary::Periodic, Boundary::Reflecting] {
+            assert_eq!(boundary.resolve(3, 5), Some(3));
+        }
+        assert_eq!(Boundary::Wall.resolve(-1, 5), None);
+        assert_eq!(Boundary::Wall.resolve(5, 5), None);
+        assert_eq!(Boundary::Periodic.resolve(-1, 5), Some(4));
+        assert_eq!(Boundary::Periodic.resolve(7, 5), Some(2));
+        assert_eq!(Boundary::Reflecting.resolve(-1, 5), Some(1));
+        assert_eq!(Boundary::Reflecting.resolve(5, 5), Some(3));
+        assert_eq!(Boundary::Reflecting.resolve(-3, 5), Some(3));
+        assert_eq!(Boundary::Reflecting.resolve(1, 1), Some(0));
+
+        let mut config = SimConfig::default();
+        config.set_override("boundary=periodic").unwrap();
+        config.set_override("boundary_z=reflecting").unwrap();
+        assert_eq!(
+            config.boundaries(),
+            [Boundary::Periodic, Boundary::Periodic, Boundary::Reflecting]
+        );
+        assert_eq!(config.wrap(), (true, true));
+    }
+
+    #[test]
+    fn test_neighborhood_offsets() {
+        let expected = [
+            (Neighborhood::VonNeumann, 6),
+            (Neighborhood::Moore, 26),
+            (Neighborhood::Plane4, 4),
+            (Neighborhood::Plane8, 8),
+        ];
+        for (neighborhood, count) in expected {
+            let offsets = neighborhood.offsets().unwrap();
+            assert_eq!(offsets.len(), count, "{:?}", neighborhood);
+            let mut unique = offsets.to_vec();
+            unique.sort_unstable();
+            unique.dedup();
+            assert_eq!(unique.len(), count, "{:?} has duplicates", neighborhood);
+            for &(dx, dy, dz) in offsets {
+                assert_eq!(dx.abs().max(dy.abs()).max(dz.abs()), 1);
+            }
+        }
+        for &(_, _, dz) in Neighborhood::Plane8.offsets().unwrap() {
+            assert_eq!(dz, 0);
+        }
+        assert!(Neighborhood::LongRange.offsets().is_none());
+        assert_eq!("plane8".parse(), Ok(Neighborhood::Plane8));
+    }
+
+    #[test]
+    fn test_scheduled_temperature() {
+        let config = SimConfig {
+            temperature: 2.0,
+            final_temperature: 0.5,
+            cooling_steps: 100,
+            cooling_rate: 0.5,
+            ..SimConfig::default()
+        };
+        let at = |schedule, elapsed| {
+            SimConfig {
+                schedule,
+                ..config.clone()
+            }
+            .scheduled_temperature(elapsed)
+        };
+
+        assert_eq!(at(Schedule::Constant, 1_000), 2.0);
+        assert_eq!(at(Schedule::Linear, 0), 2.0);
+        assert_eq!(at(Schedule::Linear, 50), 1.25);
+        assert_eq!(at(Schedule::Linear, 1_000), 0.5);
+        assert_eq!(at(Schedule::Exponential, 3), 0.25);
+        assert_eq!(at(Schedule::Logarithmic, 0), 2.0);
+        assert!((at(Schedule::Logarithmic, 1) - 2.0 / (1.0 + 2f64.ln())).abs() < 1e-12);
+        assert!(at(Schedule::Logarithmic, 1_000_000) < 0.15);
+
+        let mut config = SimConfig::default();
+        config.set_override("schedule=exponential").unwrap();
+        assert_eq!(config.schedule, Schedule::Exponential);
+        config.cooling_rate = 1.5;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_probability() {
+        let config = SimConfig {
+            probability_anyway: 1.5,
+            ..SimConfig::default()
+        };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_bitmap_must_fit() {
+        let config = SimConfig {
+            grid_size: GridSize::cube(4),
+            ..SimConfig::default()
+        };
+        assert!(config
+            .validate_bitmap("bmp", &Array2::from_elem((4, 4), true))
+            .is_ok());
+        assert!(config
+            .validate_bitmap("bmp", &Array2::from_elem((5, 2), true))
+            .is_err());
+        assert!(config
+            .validate_bitmap("bmp", &Array2::from_elem((0, 0), true))
+            .is_err());
+    }
+}
@@ -0,0 +1,209 @@
+use crate::batch::{run_batch, BatchOptions};
+use crate::config::{ConfigError, SimConfig};
+use crate::state::GameState;
+use crate::stats::{summarize, Summary};
+use ndarray::Array2;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::io::{self, Write};
+
+/// How many replicas to run and when each of them stops
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExperimentOptions {
+    pub replicas: usize,
+    /// Replica `i` is seeded with `replica_seed(base_seed, i)`
+    pub base_seed: u64,
+    pub batch: BatchOptions,
+}
+
+/// Outcome of a single replica
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReplicaOutcome {
+    pub replica: usize,
+    pub seed: u64,
+    pub steps: usize,
+    /// Step of the first completed pattern, if any pattern completed
+    pub first_completion: Option<usize>,
+    pub completions: usize,
+    pub fill_ratio: f64,
+    pub display_fill_ratio: f64,
+    pub temperature: f64,
+}
+
+/// Aggregate of one metric over all replicas
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MetricRow {
+    pub metric: &'static str,
+    /// Replicas the metric is undefined for (e.g. no completion before the step limit)
+    pub missing: usize,
+    pub summary: Option<Summary>,
+}
+
+/// Derive the seed of replica `index` from the experiment's base seed
+///
+/// Uses the SplitMix64 finaliser so neighbouring replicas get unrelated streams.
+pub fn replica_seed(base_seed: u64, index: usize) -> u64 {
+    let mut z = base_seed.wrapping_add((index as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Run `options.replicas` independently seeded simulations in parallel
+///
+/// Outcomes are returned in replica order regardless of scheduling.
+pub fn run_experiment(
+    config: &SimConfig,
+    bmp: &Array2<bool>,
+    player_bmp: &Array2<bool>,
+    options: &ExperimentOptions,
+) -> Result<Vec<ReplicaOutcome>, ConfigError> {
+    // Validate once up front instead of failing inside the workers
+    config.validate()?;
+    config.validate_bitmap("main bitmap", bmp)?;
+    config.validate_bitmap("player bitmap", player_bmp)?;
+
+    let outcomes = (0..options.replicas)
+        .into_par_iter()
+        .map(|replica| {
+            let seed = replica_seed(options.base_seed, replica);
+            let mut game_state =
+                GameState::from_bitmaps(config.clone(), bmp.clone(), player_bmp.clone(), seed)
+                    .expect("configuration was validated above");
+            let summary = run_batch(&mut game_state, &options.batch);
+
+            ReplicaOutcome {
+                replica,
+                seed,
+                steps: summary.steps,
+                first_completion: summary.completions.first().map(|c| c.step),
+                completions: summary.completions.len(),
+                fill_ratio: summary.fill_ratio,
+                display_fill_ratio: summary.display_fill_ratio,
+                temperature: summary.temperature,
+            }
+        })
+        .collect();
+
+    Ok(outcomes)
+}
+
+/// Means, variances and confidence intervals of every replica metric
+pub fn aggregate(outcomes: &[ReplicaOutcome]) -> Vec<MetricRow> {
+    let metric = |name: &'static str, values: Vec<f64>| MetricRow {
+        metric: name,
+        missing: outcomes.len() - values.len(),
+        summary: summarize(&values),
+    };
+
+    vec![
+        metric(
+            "first_completion_step",
+            outcomes
+                .iter()
+                .filter_map(|o| o.first_completion.map(|step| step as f64))
+                .collect(),
+        ),
+        metric(
+            "completions",
+            outcomes.iter().map(|o| o.completions as f64).collect(),
+        ),
+        metric("steps", outcomes.iter().map(|o| o.steps as f64).collect()),
+        metric(
+            "display_fill_ratio",
+            outcomes.iter().map(|o| o.display_fill_ratio).collect(),
+        ),
+    ]
+}
+
+/// Write the aggregate table as tab-separated values with a header row
+pub fn write_table<W: Write>(mut writer: W, rows: &[MetricRow]) -> io::Result<()> {
+    writeln!(
+        writer,
+        "metric\tn\tmissing\tmean\tvariance\tci95_low\tci95_high"
+    )?;
+    for row in rows {
+        match &row.summary {
+            Some(s) => writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                row.metric, s.n, row.missing, s.mean, s.variance, s.ci95.0, s.ci95.1
+            )?,
+            None => writeln!(writer, "{}\t0\t{}\t\t\t\t", row.metric, row.missing)?,
+        }
+    }
+    Ok(())
+}
+
+/// Write one tab-separated line per replica with a header row
+pub fn write_replicas<W: Write>(mut writer: W, outcomes: &[ReplicaOutcome]) -> io::Result<()> {
+    writeln!(
+        writer,
+        "replica\tseed\tsteps\tfirst_completion\tcompletions\tfill_ratio\tdisplay_fill_ratio\ttemperature"
+    )?;
+    for o in outcomes {
+        let first_completion = o
+            .first_completion
+            .map(|step| step.to_string())
+            .unwrap_or_default();
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            o.replica,
+            o.seed,
+            o.steps,
+            first_completion,
+            o.completions,
+            o.fill_ratio,
+            o.display_fill_ratio,
+            o.temperature
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::small_game_state;
+
+    #[test]
+    fn test_replica_seeds_are_distinct() {
+        let seeds: Vec<u64> = (0..1000).map(|i| replica_seed(42, i)).collect();
+        let mut unique = seeds.clone();
+        unique.sort_unstable();
+        unique.dedup();
+        assert_eq!(unique.len(), seeds.len());
+    }
+
+    #[test]
+    fn test_experiment_is_reproducible() {
+        let game_state = small_game_state(3, |_| {});
+        let (config, bmp) = (&game_state.config, &game_state.bmp);
+        let options = ExperimentOptions {
+            replicas: 6,
+            base_seed: 3,
+            batch: BatchOptions {
+                max_steps: Some(20_000),
+                target_completions: Some(2),
+            },
+        };
+
+        let a = run_experiment(config, bmp, bmp, &options).unwrap();
+        let b = run_experiment(config, bmp, bmp, &options).unwrap();
+        assert_eq!(a, b);
+        assert_eq!(a.len(), 6);
+        assert!(a.iter().enumerate().all(|(i, o)| o.replica == i));
+
+        let rows = aggregate(&a);
+        let completions = rows.iter().find(|r| r.metric == "completions").unwrap();
+        assert_eq!(completions.summary.as_ref().unwrap().n, 6);
+
+        let mut table = Vec::new();
+        write_table(&mut table, &rows).unwrap();
+        assert_eq!(
+            String::from_utf8(table).unwrap().lines().count(),
+            rows.len() + 1
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use crate::state::{Point2, Point3};
+use ndarray::*;
+
+/// Whether windows wrap around the grid along x and y (periodic boundaries)
+pub type Wrap = (bool, bool);
+
+/// No wrapping along either axis
+pub const NO_WRAP: Wrap = (false, false);
+
+/// Fraction of bits in the z=0 window at `cords` that match `bmp`
+///
+/// Returns 0.0 if the bitmap would not fit inside the grid at `cords`.
+pub fn goodness(cords: &Point2, side: &Array3<bool>, bmp: &Array2<bool>, wrap: Wrap) -> f32 {
+    match matching_bits(cords, side, bmp, wrap) {
+        Some(tot) => tot as f32 / (bmp.dim().0 * bmp.dim().1) as f32,
+        None => 0.0,
+    }
+}
+
+/// Number of bits in the z=0 window at `cords` that match `bmp`
+///
+/// Returns `None` if the bitmap would not fit inside the grid at `cords`.
+pub fn matching_bits(
+    cords: &Point2,
+    side: &Array3<bool>,
+    bmp: &Array2<bool>,
+    wrap: Wrap,
+) -> Option<usize> {
+    matching_bits_on_layer(cords, 0, side, bmp, wrap)
+}
+
+/// Number of bits in the window at `cords` on layer `z` that match `bmp`
+///
+/// Along an axis that wraps the window may run across the edge of the grid.
+/// Returns `None` if the bitmap would not fit inside the grid at `cords`.
+pub fn matching_bits_on_layer(
+    cords: &Point2,
+    z: usize,
+    side: &Array3<bool>,
+    bmp: &Array2<bool>,
+    wrap: Wrap,
+) -> Option<usize> {
+    matching_voxels(
+        &(cords.0, cords.1, z),
+        side,
+        bmp.view().insert_axis(Axis(2)),
+        wrap,
+    )
+}
+
+/// Fraction of voxels in the box at `cords` that match `pattern`
+///
+/// Returns 0.0 if the pattern would not fit inside the grid at `cords`.
+pub fn goodness_3d(
+    cords: &Point3,
+    side: &Array3<bool>,
+    pattern: ArrayView3<bool>,
+    wrap: Wrap,
+) -> f32 {
+    match matching_voxels(cords, side, pattern, wrap) {
+        Some(tot) => tot as f32 / pattern.len() as f32,
+        None => 0.0,
+    }
+}
+
+/// Number of voxels in the box at `cords` that match `pattern`
+///
+/// The box may run across the edge of the grid along x and y like a
+/// wrapping window, but never along z. Returns `None` if the pattern would
+/// not fit inside the grid at `cords`.
+pub fn matching_voxels(
+    cords: &Point3,
+    side: &Array3<bool>,
+    pattern: ArrayView3<bool>,
+    wrap: Wrap,
+) -> Option<usize> {
+    let (rows, cols, depth) = side.dim();
+    let (height, width, thickness) = pattern.dim();
+    if !fits(cords.0, height, rows, wrap.0)
+        || !fits(cords.1, width, cols, wrap.1)
+        || !fits(cords.2, thickness, depth, false)
+    {
+        return None;
+    }
+
+    // Count matching bits, taking indices modulo the grid for wrapped windows
+    let tot = pattern
+        .indexed_iter()
+        .filter(|&((i, j, k), &bit)| {
+            side[[(cords.0 + i) % rows, (cords.1 + j) % cols, cords.2 + k]] == bit
+        })
+        .count();
+
+    Some(tot)
+}
+
+/// Whether the window of `shape` at `cords` covers `point` on a layer of `dims`
+pub fn window_contains(
+    cords: &Point2,
+    shape: (usize, usize),
+    point: Point2,
+    dims: (usize, usize),
+    wrap: Wrap,
+) -> bool {
+    axis_offset(cords.0, point.0, dims.0, wrap.0).is_some_and(|offset| offset < shape.0)
+        && axis_offset(cords.1, point.1, dims.1, wrap.1).is_some_and(|offset| offset < shape.1)
+}
+
+/// Whether two windows on a layer of `dims` share at least one cell
+pub fn windows_overlap(
+    a: &Point2,
+    a_shape: (usize, usize),
+    b: &Point2,
+    b_shape: (usize, usize),
+    dims: (usize, usize),
+    wrap: Wrap,
+) -> bool {
+    axis_overlaps((a.0, a_shape.0), (b.0, b_shape.0), dims.0, wrap.0)
+        && axis_overlaps((a.1, a_shape.1), (b.1, b_shape.1), dims.1, wrap.1)
+}
+
+// Whether two (start, extent) intervals on an axis of `len` overlap
+fn axis_overlaps(a: (usize, usize), b: (usize, usize), len: usize, wrap: bool) -> bool {
+    axis_offset(a.0, b.0, len, wrap).is_some_and(|offset| offset < a.1)
+        || axis_offset(b.0, a.0, len, wrap).is_some_and(|offset| offset < b.1)
+}
+
+// Whether a window of `extent` starting at `start` fits on an axis of `len`
+fn fits(start: usize, extent: usize, len: usize, wrap: bool) -> bool {
+    if wrap {
+        start < len && extent <= len
+    } else {
+        start + extent <= len
+    }
+}
+
+// Distance from `start` forward to `point` along an axis of `len`, if `point`
+// is not behind `start`
+fn axis_offset(start: usize, point: usize, len: usize, wrap: bool) -> Option<usize> {
+    if wrap {
+        Some((point + len - start % len) % len)
+    } else {
+        point.checked_sub(start)
+    }
+}
+
+/// Element-wise XOR of two equally shaped bitmaps
+pub fn xor(slice: &Array2<bool>, bmp: &Array2<bool>) -> Array2<bool> {
+    // Create a result array with the same dimensions as the input arrays
+    let mut result = Array2::from_elem(slice.dim(), false);
+    for ((i, j), val) in result.indexed_iter_mut() {
+        *val = slice[[i, j]] ^ bmp[[i, j]];
+    }
+    result
+}
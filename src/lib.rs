@@ -0,0 +1,35 @@
+//! Simulation core of the Anscombe box.
+//!
+//! A [`state::GameState`] holds a 3D grid of bits, the pattern [`site`]s the
+//! dynamics try to form and the [`player`]. Build one with
+//! [`state::GameState::from_bitmaps`] and advance it with
+//! [`state::GameState::step`], or run it to completion headlessly with
+//! [`batch::run_batch`]. [`experiment::run_experiment`] runs many independently
+//! seeded replicas in parallel, and [`compare::run_comparison`] runs a guided
+//! simulation next to an unguided control. The interactive cursive frontend
+//! lives in [`tui`] behind the `tui` feature.
+
+pub mod analysis;
+pub mod batch;
+pub mod bitmap_loader;
+pub mod compare;
+pub mod config;
+pub mod experiment;
+pub mod goodness;
+pub mod player;
+pub mod projection;
+pub mod site;
+pub mod state;
+pub mod stats;
+#[cfg(feature = "tui")]
+pub mod tui;
+
+// Compiles the README's examples as doctests
+#[cfg(doctest)]
+#[doc = include_str!("../README.md")]
+struct ReadmeDoctests;
+
+#[cfg(test)]
+mod test_bitmap_loading;
+#[cfg(test)]
+mod tests;
@@ -1,6 +1,40 @@
 use crate::state::Point2;
 use ndarray::*;
 
+/// Cells covered by one fast (shifted) move
+pub const FAST_MOVE: usize = 5;
+
+/// A direction the player can move in; up is towards row (x) 0 and left
+/// towards column (y) 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+}
+
+impl Direction {
+    /// Change in (x, y) for a move of one cell
+    pub fn offset(self) -> (isize, isize) {
+        match self {
+            Direction::Up => (-1, 0),
+            Direction::Down => (1, 0),
+            Direction::Left => (0, -1),
+            Direction::Right => (0, 1),
+            Direction::UpLeft => (-1, -1),
+            Direction::UpRight => (-1, 1),
+            Direction::DownLeft => (1, -1),
+            Direction::DownRight => (1, 1),
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Player {
     pub position: Point2,
     pub bitmap: Array2<bool>,
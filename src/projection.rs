@@ -0,0 +1,173 @@
+use crate::state::{Point2, Point3};
+use ndarray::*;
+use std::fmt;
+
+/// A way to flatten the 3D grid into a 2D image for display
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Projection {
+    /// The x-y plane at depth `z`
+    Layer(usize),
+    /// Whether any cell down each z column is set (max / OR projection)
+    Max,
+    /// Fraction of set cells down each z column
+    Mean,
+    /// The x-z plane at column `y` (rows are x, columns are z)
+    XzSection(usize),
+    /// The y-z plane at row `x` (rows are y, columns are z)
+    YzSection(usize),
+}
+
+impl Projection {
+    /// Intensity of every pixel in [0, 1]
+    ///
+    /// An index past the end of its axis shows the last plane.
+    pub fn render(self, state: &Array3<bool>) -> Array2<f32> {
+        let (rows, cols, depth) = state.dim();
+        let as_intensity = |plane: ArrayView2<bool>| plane.mapv(|b| b as u8 as f32);
+        match self {
+            Projection::Layer(z) => as_intensity(state.index_axis(Axis(2), z.min(depth - 1))),
+            Projection::Max => {
+                state.map_axis(Axis(2), |column| column.iter().any(|&b| b) as u8 as f32)
+            }
+            Projection::Mean => state.map_axis(Axis(2), |column| {
+                column.iter().filter(|&&b| b).count() as f32 / depth as f32
+            }),
+            Projection::XzSection(y) => as_intensity(state.index_axis(Axis(1), y.min(cols - 1))),
+            Projection::YzSection(x) => as_intensity(state.index_axis(Axis(0), x.min(rows - 1))),
+        }
+    }
+
+    /// Where this projection shows a box of `extent` cells at `origin`
+    ///
+    /// Returns the same projection in the box's own coordinates, so rendering
+    /// the box's contents with it gives the part that is visible, together with
+    /// the image pixel of the box's corner. None if the sliced plane misses the
+    /// box. Offsets along x and y are taken modulo the grid dimensions `dims`,
+    /// so boxes that wrap around a periodic edge are handled too.
+    pub fn clip(
+        self,
+        origin: Point3,
+        extent: (usize, usize, usize),
+        dims: (usize, usize, usize),
+    ) -> Option<(Projection, Point2)> {
+        let (x0, y0, z0) = origin;
+        let inside = |c: usize, start: usize, len: usize, dim: usize| {
+            let offset = (c % dim + dim - start % dim) % dim;
+            (offset < len).then_some(offset)
+        };
+        match self {
+            Projection::Layer(z) => {
+                let offset = z.checked_sub(z0).filter(|&o| o < extent.2)?;
+                Some((Projection::Layer(offset), (x0, y0)))
+            }
+            Projection::Max | Projection::Mean => Some((self, (x0, y0))),
+            Projection::XzSection(y) => {
+                let offset = inside(y, y0, extent.1, dims.1)?;
+                Some((Projection::XzSection(offset), (x0, z0)))
+            }
+            Projection::YzSection(x) => {
+                let offset = inside(x, x0, extent.0, dims.0)?;
+                Some((Projection::YzSection(offset), (y0, z0)))
+            }
+        }
+    }
+
+    /// Grid axes (0 for x, 1 for y, 2 for z) along the image's rows and columns
+    pub fn axes(self) -> (usize, usize) {
+        match self {
+            Projection::Layer(_) | Projection::Max | Projection::Mean => (0, 1),
+            Projection::XzSection(_) => (0, 2),
+            Projection::YzSection(_) => (1, 2),
+        }
+    }
+}
+
+impl fmt::Display for Projection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Projection::Layer(z) => write!(f, "layer z={}", z),
+            Projection::Max => write!(f, "max over z"),
+            Projection::Mean => write!(f, "mean over z"),
+            Projection::XzSection(y) => write!(f, "XZ section y={}", y),
+            Projection::YzSection(x) => write!(f, "YZ section x={}", x),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2x3x4 grid with a single set cell at (1, 2, 3) and the whole z=0 layer set
+    fn grid() -> Array3<bool> {
+        let mut state = Array3::from_elem((2, 3, 4), false);
+        state.slice_mut(s![.., .., 0]).fill(true);
+        state[[1, 2, 3]] = true;
+        state
+    }
+
+    #[test]
+    fn test_shapes() {
+        let state = grid();
+        assert_eq!(Projection::Layer(1).render(&state).dim(), (2, 3));
+        assert_eq!(Projection::Max.render(&state).dim(), (2, 3));
+        assert_eq!(Projection::XzSection(0).render(&state).dim(), (2, 4));
+        assert_eq!(Projection::YzSection(0).render(&state).dim(), (3, 4));
+    }
+
+    #[test]
+    fn test_values() {
+        let state = grid();
+        assert!(Projection::Layer(0)
+            .render(&state)
+            .iter()
+            .all(|&v| v == 1.0));
+        assert_eq!(Projection::Layer(3).render(&state)[[1, 2]], 1.0);
+        assert_eq!(Projection::Layer(3).render(&state)[[0, 0]], 0.0);
+        assert!(Projection::Max.render(&state).iter().all(|&v| v == 1.0));
+
+        let mean = Projection::Mean.render(&state);
+        assert_eq!(mean[[0, 0]], 0.25);
+        assert_eq!(mean[[1, 2]], 0.5);
+
+        let xz = Projection::XzSection(2).render(&state);
+        assert_eq!(xz.row(1).to_vec(), vec![1.0, 0.0, 0.0, 1.0]);
+        let yz = Projection::YzSection(1).render(&state);
+        assert_eq!(yz.row(2).to_vec(), vec![1.0, 0.0, 0.0, 1.0]);
+
+        // Out-of-range indices show the last plane
+        assert_eq!(
+            Projection::Layer(99).render(&state),
+            Projection::Layer(3).render(&state)
+        );
+    }
+
+    #[test]
+    fn test_clip() {
+        let dims = (10, 10, 5);
+        let (origin, extent) = ((8, 2, 1), (4, 3, 2));
+
+        assert_eq!(Projection::Layer(0).clip(origin, extent, dims), None);
+        assert_eq!(
+            Projection::Layer(2).clip(origin, extent, dims),
+            Some((Projection::Layer(1), (8, 2)))
+        );
+        assert_eq!(Projection::Layer(3).clip(origin, extent, dims), None);
+        assert_eq!(
+            Projection::Mean.clip(origin, extent, dims),
+            Some((Projection::Mean, (8, 2)))
+        );
+        assert_eq!(
+            Projection::XzSection(4).clip(origin, extent, dims),
+            Some((Projection::XzSection(2), (8, 1)))
+        );
+        assert_eq!(Projection::XzSection(5).clip(origin, extent, dims), None);
+
+        // The box covers x = 8, 9, 0, 1 across the periodic edge
+        assert_eq!(
+            Projection::YzSection(1).clip(origin, extent, dims),
+            Some((Projection::YzSection(3), (2, 1)))
+        );
+        assert_eq!(Projection::YzSection(2).clip(origin, extent, dims), None);
+    }
+}
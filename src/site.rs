@@ -1,14 +1,21 @@
-use crate::state::{Point2, PATTERN_COMPLETION_THRESHOLD};
+use crate::goodness::{window_contains, windows_overlap, Wrap};
+use crate::state::{Point2, Point3};
 use ndarray::*;
 
 /// Represents a site where a pattern can be formed
+#[derive(Clone)]
 pub struct Site {
     /// Position of the site in the grid
     pub position: Point2,
-    /// Custom bitmap for this site (if None, uses default)
-    pub custom_bitmap: Option<Array2<bool>>,
+    /// Layer the site's pattern starts on (0 is the display layer)
+    pub z: usize,
+    /// Custom pattern for this site, one bitmap per layer from `z` down (if
+    /// None, uses the default bitmap on layer `z` alone)
+    pub custom_bitmap: Option<Array3<bool>>,
     /// Whether this site is active (being used for pattern matching)
     pub is_active: bool,
+    /// Simulation step at which the site was placed
+    pub created_at: usize,
 }
 
 impl Site {
@@ -16,31 +23,46 @@ impl Site {
     pub fn new(position: Point2) -> Self {
         Self {
             position,
+            z: 0,
             custom_bitmap: None,
             is_active: true,
+            created_at: 0,
         }
     }
 
     /// Create a new site with a custom bitmap
     pub fn with_custom_bitmap(position: Point2, bitmap: Array2<bool>) -> Self {
+        Self::with_custom_volume((position.0, position.1, 0), bitmap.insert_axis(Axis(2)))
+    }
+
+    /// Create a new site with a custom voxel pattern whose top layer is at `position.2`
+    pub fn with_custom_volume(position: Point3, volume: Array3<bool>) -> Self {
         Self {
-            position,
-            custom_bitmap: Some(bitmap),
+            position: (position.0, position.1),
+            z: position.2,
+            custom_bitmap: Some(volume),
             is_active: true,
+            created_at: 0,
         }
     }
 
-    /// Get the bitmap for this site (custom or default)
-    pub fn get_bitmap<'a>(&'a self, default_bitmap: &'a Array2<bool>) -> &'a Array2<bool> {
+    /// Get the pattern for this site (custom or default), a single layer for flat sites
+    pub fn get_pattern<'a>(&'a self, default_bitmap: &'a Array2<bool>) -> ArrayView3<'a, bool> {
         match &self.custom_bitmap {
-            Some(custom) => custom,
-            None => default_bitmap,
+            Some(custom) => custom.view(),
+            None => default_bitmap.view().insert_axis(Axis(2)),
         }
     }
 
-    /// Check if the pattern at this site is complete (takes goodness as parameter)
-    pub fn is_complete(&self, goodness: f32) -> bool {
-        goodness > PATTERN_COMPLETION_THRESHOLD
+    /// Position of the pattern's first voxel
+    pub fn origin(&self) -> Point3 {
+        (self.position.0, self.position.1, self.z)
+    }
+
+    /// Check if the pattern at this site is complete (takes goodness and the
+    /// configured completion threshold as parameters)
+    pub fn is_complete(&self, goodness: f32, threshold: f32) -> bool {
+        goodness > threshold
     }
 
     /// Deactivate this site (pattern is complete)
@@ -58,14 +80,30 @@ impl Site {
         self.position = new_position;
     }
 
-    /// Get the dimensions of this site's bitmap
-    pub fn get_dimensions<'a>(&'a self, default_bitmap: &'a Array2<bool>) -> (usize, usize) {
-        let bitmap = self.get_bitmap(default_bitmap);
-        (bitmap.dim().0, bitmap.dim().1)
+    /// Get the dimensions of this site's pattern on a layer
+    pub fn get_dimensions(&self, default_bitmap: &Array2<bool>) -> (usize, usize) {
+        let (rows, cols, _) = self.get_extent(default_bitmap);
+        (rows, cols)
+    }
+
+    /// Get the (rows, cols, layers) extent of this site's pattern
+    pub fn get_extent(&self, default_bitmap: &Array2<bool>) -> (usize, usize, usize) {
+        self.get_pattern(default_bitmap).dim()
+    }
+
+    /// Whether this site's pattern covers only the z=0 display layer
+    pub fn is_on_display_layer(&self, default_bitmap: &Array2<bool>) -> bool {
+        self.is_flat_on_layer(0, default_bitmap)
+    }
+
+    /// Whether this site's pattern covers only layer `z`
+    pub fn is_flat_on_layer(&self, z: usize, default_bitmap: &Array2<bool>) -> bool {
+        self.z == z && self.get_extent(default_bitmap).2 == 1
     }
 }
 
 /// Collection of sites with helper methods
+#[derive(Clone, Default)]
 pub struct SiteManager {
     sites: Vec<Site>,
 }
@@ -77,13 +115,23 @@ impl SiteManager {
     }
 
     /// Add a new site with default bitmap
-    pub fn add_site(&mut self, position: Point2) {
-        self.sites.push(Site::new(position));
+    pub fn add_site(&mut self, position: Point2) -> &mut Site {
+        self.push(Site::new(position))
     }
 
     /// Add a new site with custom bitmap
-    pub fn add_custom_site(&mut self, position: Point2, bitmap: Array2<bool>) {
-        self.sites.push(Site::with_custom_bitmap(position, bitmap));
+    pub fn add_custom_site(&mut self, position: Point2, bitmap: Array2<bool>) -> &mut Site {
+        self.push(Site::with_custom_bitmap(position, bitmap))
+    }
+
+    /// Add a new site with a custom voxel pattern
+    pub fn add_volume_site(&mut self, position: Point3, volume: Array3<bool>) -> &mut Site {
+        self.push(Site::with_custom_volume(position, volume))
+    }
+
+    fn push(&mut self, site: Site) -> &mut Site {
+        self.sites.push(site);
+        self.sites.last_mut().expect("a site was just pushed")
     }
 
     /// Get all active sites
@@ -109,55 +157,96 @@ impl SiteManager {
         &mut self.sites
     }
 
+    /// Check if a box of `extent` at `position` collides with any active
+    /// site on a display layer of `dims`
+    pub fn collides_with_sites(
+        &self,
+        position: Point3,
+        extent: (usize, usize, usize),
+        default_bitmap: &Array2<bool>,
+        dims: (usize, usize),
+        wrap: Wrap,
+    ) -> bool {
+        !self
+            .find_colliding_sites(position, extent, default_bitmap, dims, wrap)
+            .is_empty()
+    }
+
+    /// Indices of the active sites a box of `extent` at `position` collides
+    /// with on a display layer of `dims`
+    pub fn find_colliding_sites(
+        &self,
+        position: Point3,
+        extent: (usize, usize, usize),
+        default_bitmap: &Array2<bool>,
+        dims: (usize, usize),
+        wrap: Wrap,
+    ) -> Vec<usize> {
+        let collides = |site: &Site| {
+            let (rows, cols, layers) = site.get_extent(default_bitmap);
+            // Sites on disjoint layers never collide
+            site.z < position.2 + extent.2
+                && position.2 < site.z + layers
+                && windows_overlap(
+                    &(position.0, position.1),
+                    (extent.0, extent.1),
+                    &site.position,
+                    (rows, cols),
+                    dims,
+                    wrap,
+                )
+        };
+        (0..self.sites.len())
+            .filter(|&i| self.sites[i].is_active && collides(&self.sites[i]))
+            .collect()
+    }
+
+    /// Index of the site whose window covers `point` on the display layer,
+    /// preferring active sites and then the most recently added
+    pub fn find_site_covering(
+        &self,
+        point: Point2,
+        default_bitmap: &Array2<bool>,
+        dims: (usize, usize),
+        wrap: Wrap,
+    ) -> Option<usize> {
+        (0..self.sites.len())
+            .filter(|&i| {
+                let site = &self.sites[i];
+                window_contains(
+                    &site.position,
+                    site.get_dimensions(default_bitmap),
+                    point,
+                    dims,
+                    wrap,
+                )
+            })
+            .max_by_key(|&i| (self.sites[i].is_active, i))
+    }
+
+    /// Remove the site at `index` in `get_all_sites`
+    pub fn remove_site(&mut self, index: usize) -> Site {
+        self.sites.remove(index)
+    }
+
     /// Find a site at a specific position
     pub fn find_site_at(&self, position: Point2) -> Option<&Site> {
-        self.sites.iter().find(|site| site.position == position)
+        self.index_of(position).map(|index| &self.sites[index])
     }
 
     /// Find a site at a specific position (mutable)
     pub fn find_site_at_mut(&mut self, position: Point2) -> Option<&mut Site> {
-        self.sites.iter_mut().find(|site| site.position == position)
+        self.index_of(position).map(|index| &mut self.sites[index])
     }
 
     /// Remove a site at a specific position
     pub fn remove_site_at(&mut self, position: Point2) -> Option<Site> {
-        if let Some(index) = self.sites.iter().position(|site| site.position == position) {
-            Some(self.sites.remove(index))
-        } else {
-            None
-        }
+        self.index_of(position).map(|index| self.remove_site(index))
     }
 
-    /// Check if a position collides with any existing site
-    pub fn collides_with_sites(
-        &self,
-        position: Point2,
-        _site_shape: (usize, usize),
-        default_bitmap: &Array2<bool>,
-    ) -> bool {
-        for site in &self.sites {
-            if !site.is_active {
-                continue;
-            }
-
-            let site_shape = site.get_dimensions(default_bitmap);
-
-            // Check if the two rectangles overlap
-            let pos_end = (position.0 + site_shape.0, position.1 + site_shape.1);
-            let site_end = (
-                site.position.0 + site_shape.0,
-                site.position.1 + site_shape.1,
-            );
-
-            if position.0 < site_end.0
-                && pos_end.0 > site.position.0
-                && position.1 < site_end.1
-                && pos_end.1 > site.position.1
-            {
-                return true;
-            }
-        }
-        false
+    // Index of the first site placed exactly at `position`
+    fn index_of(&self, position: Point2) -> Option<usize> {
+        self.sites.iter().position(|site| site.position == position)
     }
 
     /// Get the number of active sites
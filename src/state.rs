@@ -1,87 +1,417 @@
+use crate::config::{
+    offset_along, Acceptance, ConfigError, Dynamics, Neighborhood, Offset, SimConfig,
+};
+use crate::goodness::{goodness_3d, matching_bits_on_layer, matching_voxels, window_contains};
+use crate::player::{Direction, Player};
+use crate::projection::Projection;
+use crate::site::{Site, SiteManager};
 use ndarray::*;
 use rand::prelude::*;
-use crate::site::SiteManager;
-use crate::player::Player;
+use serde::Serialize;
+use std::fmt;
 
 pub type Point2 = (usize, usize);
 pub type Point3 = (usize, usize, usize);
 
-pub const GRID_SIZE: usize = 60;
-pub const N_SITES: usize = 4;
-pub const N_TRIALS: usize = 100;
-pub const DISPLAY_UPDATE_INTERVAL: usize = 11_000; // if this is too low, cursive becomes the bottleneck
-pub const PATTERN_COMPLETION_THRESHOLD: f32 = 0.98;
-pub const PROBABILITY_ANYWAY: f64 = 0.01;
-pub const PROBABILITY_EXCHANGE: f64 = 0.8;
+fn fraction_true<'a>(bits: impl ExactSizeIterator<Item = &'a bool>) -> f64 {
+    let total = bits.len();
+    let set = bits.filter(|&&b| b).count();
+    set as f64 / total as f64
+}
+
+// One random coordinate below each of `sizes`
+fn rand_point(sizes: &[usize], rng: &mut StdRng) -> Vec<usize> {
+    let mut ret: Vec<usize> = Vec::new();
+    for &size in sizes {
+        let r: f64 = rng.gen();
+        let r: usize = (r * size as f64).floor() as usize;
+        ret.push(r);
+    }
+    ret
+}
+
+// Fill a fresh grid with the same density of true bits as `bmp` and place
+// the initial sites where the random bits already resemble it
+pub fn init_state(
+    config: &SimConfig,
+    bmp: &Array2<bool>,
+    rng: &mut StdRng,
+) -> (Array3<bool>, SiteManager) {
+    let grid_size = config.grid_size;
+
+    // Count true values in bmp
+    let tot: usize = bmp.iter().map(|&b| b as usize).sum();
+    //println!("total number of true bits in bmp: {}", tot);
+    let r: f64 = tot as f64 / (bmp.dim().0 * bmp.dim().1) as f64;
+
+    // Initialize state with random bits
+    let mut state = Array3::<bool>::from_elem(grid_size.dim(), false);
+    let mut i = 0;
+    //println!("grid_size.cells() * r: {}", grid_size.cells() as f64 * r);
+
+    // flip some bits
+    while (i as f64) < grid_size.cells() as f64 * r {
+        let points = rand_point(&[grid_size.x, grid_size.y, grid_size.z], rng);
+        let (x, y, z) = (points[0], points[1], points[2]);
+
+        if !state[[x, y, z]] {
+            state[[x, y, z]] = true;
+            i += 1;
+        }
+    }
+    //println!("attempted to flip bits {} times.", i);
+
+    // Initialize sites
+    let mut sites = SiteManager::new();
+    let layer = config.site_layer;
+
+    for _ in 0..config.n_sites {
+        let (mut best_site, mut best_goodness): (Option<Point2>, f32) = (None, 0.0);
+
+        for _ in 0..config.n_trials {
+            let point = rand_point(&[grid_size.x, grid_size.y], rng);
+            let s: Point2 = (point[0], point[1]);
+
+            let collides = sites.collides_with_sites(
+                (s.0, s.1, layer),
+                (bmp.dim().0, bmp.dim().1, 1),
+                bmp,
+                grid_size.layer(),
+                config.wrap(),
+            );
+            let g = if collides {
+                0.0
+            } else {
+                matching_bits_on_layer(&s, layer, &state, bmp, config.wrap())
+                    .map_or(0.0, |matches| matches as f32 / bmp.len() as f32)
+            };
+
+            if g > best_goodness {
+                best_goodness = g;
+                best_site = Some(s);
+            }
+        }
 
+        if let Some(site_pos) = best_site {
+            sites.add_site(site_pos).z = layer;
+        }
+    }
+
+    (state, sites)
+}
+
+// A site whose pattern reached the completion threshold
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Completion {
+    // Step at which the pattern completed
+    pub step: usize,
+    pub position: Point2,
+    // Layer the site's pattern started on
+    pub z: usize,
+    // Steps between the site being placed and completing
+    pub age: usize,
+}
+
+// Why a site cannot be placed somewhere, or changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlacementError {
+    // Part of the pattern falls past a non-periodic edge of the grid
+    OffGrid,
+    // The pattern overlaps an active site
+    Overlaps,
+    // Only completed sites can be reactivated
+    AlreadyActive,
+}
+
+impl fmt::Display for PlacementError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlacementError::OffGrid => write!(f, "would run off the grid"),
+            PlacementError::Overlaps => write!(f, "would overlap an active site"),
+            PlacementError::AlreadyActive => write!(f, "is already active"),
+        }
+    }
+}
+
+impl std::error::Error for PlacementError {}
+
+// A candidate change to the grid; applying it twice restores the grid
+#[derive(Debug, Clone, Copy)]
+enum Move {
+    // Exchange two neighbouring cells (Kawasaki, conserves the number of true bits)
+    Swap(Point3, Point3),
+    // Invert one cell (Glauber, does not conserve it)
+    Flip(Point3),
+}
+
+impl Move {
+    fn apply(self, state: &mut Array3<bool>) {
+        match self {
+            Move::Swap(point, neighbor) => state.swap(point, neighbor),
+            Move::Flip((x, y, z)) => state[[x, y, z]] = !state[[x, y, z]],
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct GameState {
+    pub config: SimConfig,
     pub state: Array3<bool>,
     pub sites: SiteManager,
     pub bmp: Array2<bool>,
     pub player: Player,
     step_count: usize,
+    seed: u64,
+    rng: StdRng,
+    completions: Vec<Completion>,
+    temperature: f64,
+    // Step the temperature schedule was last (re)started at, and the
+    // temperature it started from
+    schedule_start: usize,
+    schedule_temperature: f64,
+    // Moves proposed, and moves that were applied and kept
+    proposed_moves: usize,
+    accepted_moves: usize,
 }
 
 impl GameState {
-    pub fn new(state: Array3<bool>, sites: SiteManager, bmp: Array2<bool>, player: Player) -> Self {
+    // `rng` must be the stream `init_state` drew from, so that a run is fully determined by `seed`
+    pub fn new(
+        config: SimConfig,
+        state: Array3<bool>,
+        sites: SiteManager,
+        bmp: Array2<bool>,
+        player: Player,
+        seed: u64,
+        rng: StdRng,
+    ) -> Self {
         Self {
             state,
             sites,
             bmp,
             player,
             step_count: 0,
+            seed,
+            rng,
+            completions: Vec::new(),
+            temperature: config.temperature,
+            schedule_start: 0,
+            schedule_temperature: config.temperature,
+            proposed_moves: 0,
+            accepted_moves: 0,
+            config,
         }
     }
 
-    // Method to handle player movement with bounds checking
-    pub fn move_player(&mut self, direction: char) {
-        match direction {
-            'w' => {
-                if self.player.position.0 > 0 {
-                    self.player.position.0 -= 1;
-                }
-            }
+    // Build a fresh simulation from the main and player bitmaps, seeding
+    // every random draw (including `init_state`) from `seed`
+    pub fn from_bitmaps(
+        config: SimConfig,
+        bmp: Array2<bool>,
+        player_bmp: Array2<bool>,
+        seed: u64,
+    ) -> Result<Self, ConfigError> {
+        config.validate()?;
+        config.validate_bitmap("main bitmap", &bmp)?;
+        config.validate_bitmap("player bitmap", &player_bmp)?;
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        let (state, sites) = init_state(&config, &bmp, &mut rng);
+        let player = Player::new((0, 0), player_bmp);
+        Ok(Self::new(config, state, sites, bmp, player, seed, rng))
+    }
 
-            'a' => {
-                if self.player.position.1 > 0 {
-                    self.player.position.1 -= 1;
-                }
-            }
-            's' => {
-                if self.player.position.0 < GRID_SIZE - 1 {
-                    self.player.position.0 += 1;
-                }
-            }
+    // Move the player `distance` cells in `direction`, keeping its whole
+    // bitmap on the grid (wrapping around periodic axes instead)
+    pub fn move_player(&mut self, direction: Direction, distance: usize) {
+        let (dx, dy) = direction.offset();
+        let (x, y) = self.player.position;
+        let distance = distance as isize;
+        self.place_player((x as isize + dx * distance, y as isize + dy * distance));
+    }
 
-            'd' => {
-                if self.player.position.1 < GRID_SIZE - 1 {
-                    self.player.position.1 += 1;
-                }
+    // Bring the player back onto the grid after its bitmap changed, so the
+    // whole bitmap fits again
+    pub fn clamp_player(&mut self) {
+        let (x, y) = self.player.position;
+        self.place_player((x as isize, y as isize));
+    }
+
+    // Put the player as near `target` as its whole bitmap fits, wrapping
+    // around periodic axes instead
+    fn place_player(&mut self, target: (isize, isize)) {
+        let (rows, cols) = self.player.bitmap.dim();
+        let (wrap_x, wrap_y) = self.config.wrap();
+        let place = |target: isize, len: usize, dim: usize, wraps: bool| {
+            if wraps {
+                target.rem_euclid(dim as isize) as usize
+            } else {
+                target.clamp(0, dim.saturating_sub(len) as isize) as usize
             }
-            _ => {}
+        };
+        self.player.position = (
+            place(target.0, rows, self.config.grid_size.x, wrap_x),
+            place(target.1, cols, self.config.grid_size.y, wrap_y),
+        );
+    }
+
+    // Where a site forced by the player would start: its position on the
+    // site layer
+    pub fn player_origin(&self) -> Point3 {
+        let (x, y) = self.player.position;
+        (x, y, self.config.site_layer)
+    }
+
+    // Check whether a site with the player's bitmap fits at the player position
+    pub fn check_player_site(&self) -> Result<(), PlacementError> {
+        let (rows, cols) = self.player.bitmap.dim();
+        self.check_placement(self.player_origin(), (rows, cols, 1), None)
+    }
+
+    // Check whether a box of `extent` at `origin` stays on the grid and clear
+    // of every active site except the one at `index`
+    fn check_placement(
+        &self,
+        origin: Point3,
+        extent: (usize, usize, usize),
+        except: Option<usize>,
+    ) -> Result<(), PlacementError> {
+        let (wrap_x, wrap_y) = self.config.wrap();
+        let fits = |start: usize, len: usize, dim: usize, wraps: bool| wraps || start + len <= dim;
+        let grid_size = self.config.grid_size;
+        if !fits(origin.0, extent.0, grid_size.x, wrap_x)
+            || !fits(origin.1, extent.1, grid_size.y, wrap_y)
+            || !fits(origin.2, extent.2, grid_size.z, false)
+        {
+            return Err(PlacementError::OffGrid);
+        }
+        let overlaps = self
+            .sites
+            .find_colliding_sites(
+                origin,
+                extent,
+                &self.bmp,
+                grid_size.layer(),
+                self.config.wrap(),
+            )
+            .into_iter()
+            .any(|i| Some(i) != except);
+        if overlaps {
+            return Err(PlacementError::Overlaps);
+        }
+        Ok(())
+    }
+
+    // Method to force a new site at player position, unless it would run off
+    // the grid or overlap an active site
+    pub fn force_site(&mut self) -> Result<(), PlacementError> {
+        self.check_player_site()?;
+        let volume = self.player.bitmap.clone().insert_axis(Axis(2));
+        self.add_volume_site(self.player_origin(), volume);
+        Ok(())
+    }
+
+    // Index of the site under the player position, if any
+    pub fn find_site_under_player(&self) -> Option<usize> {
+        self.sites.find_site_covering(
+            self.player.position,
+            &self.bmp,
+            self.config.grid_size.layer(),
+            self.config.wrap(),
+        )
+    }
+
+    // Remove the site at `index` in `sites.get_all_sites()`
+    pub fn remove_site(&mut self, index: usize) -> Site {
+        self.sites.remove_site(index)
+    }
+
+    // Move the site at `index` to `position`, unless it would run off the grid
+    // or (while active) overlap another active site
+    pub fn move_site(&mut self, index: usize, position: Point2) -> Result<(), PlacementError> {
+        let site = &self.sites.get_all_sites()[index];
+        self.check_site_change(
+            index,
+            (position.0, position.1, site.z),
+            site.get_extent(&self.bmp),
+        )?;
+        self.sites.get_all_sites_mut()[index].move_to(position);
+        Ok(())
+    }
+
+    // Make a completed site at `index` active again, restarting its age;
+    // an active site keeps its age
+    pub fn reactivate_site(&mut self, index: usize) -> Result<(), PlacementError> {
+        let site = &self.sites.get_all_sites()[index];
+        if site.is_active {
+            return Err(PlacementError::AlreadyActive);
+        }
+        self.check_placement(site.origin(), site.get_extent(&self.bmp), Some(index))?;
+        let step_count = self.step_count;
+        let site = &mut self.sites.get_all_sites_mut()[index];
+        site.reactivate();
+        site.created_at = step_count;
+        Ok(())
+    }
+
+    // Give the site at `index` a new target bitmap on its top layer
+    pub fn retarget_site(
+        &mut self,
+        index: usize,
+        bitmap: Array2<bool>,
+    ) -> Result<(), PlacementError> {
+        let (rows, cols) = bitmap.dim();
+        self.check_site_change(
+            index,
+            self.sites.get_all_sites()[index].origin(),
+            (rows, cols, 1),
+        )?;
+        self.sites.get_all_sites_mut()[index].custom_bitmap = Some(bitmap.insert_axis(Axis(2)));
+        Ok(())
+    }
+
+    // Check a new box for the site at `index`; completed sites only need to
+    // stay on the grid
+    fn check_site_change(
+        &self,
+        index: usize,
+        origin: Point3,
+        extent: (usize, usize, usize),
+    ) -> Result<(), PlacementError> {
+        let result = self.check_placement(origin, extent, Some(index));
+        if result == Err(PlacementError::Overlaps) && !self.sites.get_all_sites()[index].is_active {
+            return Ok(());
         }
+        result
     }
 
-    // Method to force a new site at player position
-    pub fn force_site(&mut self) {
-        self.sites.add_custom_site(self.player.position, self.player.bitmap.clone());
+    // Add a site with a voxel pattern whose top layer is at `position.2`
+    pub fn add_volume_site(&mut self, position: Point3, volume: Array3<bool>) {
+        self.sites.add_volume_site(position, volume).created_at = self.step_count;
     }
 
     // Perform one simulation step
     pub fn step(&mut self) {
         self.step_count += 1;
+        self.temperature = self.config.scheduled_temperature_from(
+            self.schedule_temperature,
+            self.step_count - self.schedule_start,
+        );
 
         let point = self.generate_random_point_3d();
-        if let Some(neighbor) = self.find_random_neighbor(point) {
-            self.try_exchange(point, neighbor);
+        let candidate = match self.config.dynamics {
+            Dynamics::Kawasaki => match self.find_random_neighbor(point) {
+                Some(neighbor) => Move::Swap(point, neighbor),
+                None => return,
+            },
+            Dynamics::Glauber => Move::Flip(point),
+        };
+        self.proposed_moves += 1;
+        if self.try_move(candidate) {
+            self.accepted_moves += 1;
         }
     }
 
-    // Check if it's time to update the display
-    pub fn should_update_display(&self) -> bool {
-        self.step_count % DISPLAY_UPDATE_INTERVAL == 0
-    }
-
     // Get the current 2D slice for rendering
     pub fn get_render_slice(&self) -> Array2<bool> {
         self.state.slice(s![.., .., 0]).to_owned()
@@ -92,190 +422,308 @@ impl GameState {
         (self.get_render_slice(), self.player.position)
     }
 
+    // Flatten the grid into a 2D image of intensities in [0, 1]
+    pub fn get_render_projection(&self, projection: Projection) -> Array2<f32> {
+        projection.render(&self.state)
+    }
+
+    // Copy a `dim` region of the site layer starting at `origin`, wrapping
+    // around periodic axes; cells past any other edge read as false
+    pub fn grid_region(&self, origin: Point2, dim: (usize, usize)) -> Array2<bool> {
+        let (wrap_x, wrap_y) = self.config.wrap();
+        let (rows, cols) = self.config.grid_size.layer();
+        Array2::from_shape_fn(dim, |(r, c)| {
+            match (
+                offset_along(origin.0, r, rows, wrap_x),
+                offset_along(origin.1, c, cols, wrap_y),
+            ) {
+                (Some(x), Some(y)) => self.state[[x, y, self.config.site_layer]],
+                _ => false,
+            }
+        })
+    }
+
+    // Fraction of a site's pattern the grid currently matches
+    pub fn site_goodness(&self, site: &Site) -> f32 {
+        goodness_3d(
+            &site.origin(),
+            &self.state,
+            site.get_pattern(&self.bmp),
+            self.config.wrap(),
+        )
+    }
+
     // Get current step count
-    #[allow(dead_code)]
     pub fn get_step_count(&self) -> usize {
         self.step_count
     }
 
+    // Number of moves proposed so far (steps that found a valid move)
+    pub fn get_proposed_moves(&self) -> usize {
+        self.proposed_moves
+    }
+
+    // Number of proposed moves that were applied and kept
+    pub fn get_accepted_moves(&self) -> usize {
+        self.accepted_moves
+    }
+
+    // Get every completion so far, in the order they happened
+    pub fn get_completions(&self) -> &[Completion] {
+        &self.completions
+    }
+
+    // Fraction of true bits in the whole grid
+    pub fn fill_ratio(&self) -> f64 {
+        fraction_true(self.state.iter())
+    }
+
+    // Fraction of true bits on one z-layer
+    pub fn layer_fill_ratio(&self, z: usize) -> f64 {
+        fraction_true(self.state.slice(s![.., .., z]).iter())
+    }
+
+    // Get the temperature the Metropolis rule currently uses
+    pub fn get_temperature(&self) -> f64 {
+        self.temperature
+    }
+
+    // Restart the temperature schedule from `temperature`, leaving the
+    // configured temperature (which reheating returns to) alone
+    pub fn set_temperature(&mut self, temperature: f64) {
+        self.temperature = temperature;
+        self.schedule_temperature = temperature;
+        self.schedule_start = self.step_count;
+    }
+
+    // Get the seed this run was started from
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
     // Generate a random 3D point within the grid
-    fn generate_random_point_3d(&self) -> Point3 {
-        let mut rng = rand::thread_rng();
+    fn generate_random_point_3d(&mut self) -> Point3 {
+        let grid_size = self.config.grid_size;
         (
-            rng.gen_range(0..GRID_SIZE),
-            rng.gen_range(0..GRID_SIZE),
-            rng.gen_range(0..GRID_SIZE),
+            self.rng.gen_range(0..grid_size.x),
+            self.rng.gen_range(0..grid_size.y),
+            self.rng.gen_range(0..grid_size.z),
         )
     }
 
-    // Find a random valid neighbor of a point
-    fn find_random_neighbor(&self, point: Point3) -> Option<Point3> {
-        let (x, y, z) = point;
-        let mut rng = rand::thread_rng();
-
-        let directions = [
-            (1, 0, 0),
-            (-1, 0, 0),
-            (0, 1, 0),
-            (0, -1, 0),
-            (0, 0, 1),
-            (0, 0, -1),
-        ];
-
-        // Filter valid directions (within bounds)
-        let valid_neighbors: Vec<_> = directions
-            .iter()
-            .filter_map(|&(dx, dy, dz)| {
-                let nx = x as isize + dx;
-                let ny = y as isize + dy;
-                let nz = z as isize + dz;
-
-                if nx >= 0
-                    && nx < GRID_SIZE as isize
-                    && ny >= 0
-                    && ny < GRID_SIZE as isize
-                    && nz >= 0
-                    && nz < GRID_SIZE as isize
-                {
-                    Some((nx as usize, ny as usize, nz as usize))
-                } else {
-                    None
-                }
-            })
-            .collect();
+    // Find a random valid neighbor of a point in the configured neighborhood
+    fn find_random_neighbor(&mut self, point: Point3) -> Option<Point3> {
+        if self.config.neighborhood.offsets().is_none() {
+            // A long-range jump that hits a wall finds no neighbor
+            let offset = self.random_jump();
+            return self.offset_point(point, offset);
+        }
 
+        let valid_neighbors = self.neighbors(point);
         if valid_neighbors.is_empty() {
             None
         } else {
-            let idx = rng.gen_range(0..valid_neighbors.len());
+            let idx = self.rng.gen_range(0..valid_neighbors.len());
             Some(valid_neighbors[idx])
         }
     }
 
-    // Main exchange logic
-    fn try_exchange(&mut self, point: Point3, neighbor: Point3) {
-        let mut rng = rand::thread_rng();
+    // Neighbors of `point` a swap can pick from: one per offset of the
+    // neighborhood that does not cross a wall (none for long-range jumps)
+    pub fn neighbors(&self, point: Point3) -> Vec<Point3> {
+        self.config
+            .neighborhood
+            .offsets()
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|&offset| self.offset_point(point, offset))
+            .collect()
+    }
 
+    // Move `point` by `offset`, or None if that crosses a wall
+    fn offset_point(&self, point: Point3, offset: Offset) -> Option<Point3> {
+        let grid_size = self.config.grid_size;
+        let [bx, by, bz] = self.config.boundaries();
+        Some((
+            bx.resolve(point.0 as isize + offset.0, grid_size.x)?,
+            by.resolve(point.1 as isize + offset.1, grid_size.y)?,
+            bz.resolve(point.2 as isize + offset.2, grid_size.z)?,
+        ))
+    }
+
+    // Draw a long-range jump: a power-law distance, then a uniformly random
+    // offset on the cube shell at that Chebyshev distance
+    fn random_jump(&mut self) -> Offset {
+        debug_assert_eq!(self.config.neighborhood, Neighborhood::LongRange);
+        let max_jump = self.config.max_jump;
+        let exponent = self.config.jump_exponent;
+
+        let total: f64 = (1..=max_jump).map(|d| (d as f64).powf(-exponent)).sum();
+        let mut remaining = self.rng.gen::<f64>() * total;
+        let mut distance = max_jump;
+        for d in 1..max_jump {
+            remaining -= (d as f64).powf(-exponent);
+            if remaining < 0.0 {
+                distance = d;
+                break;
+            }
+        }
+
+        let d = distance as isize;
+        loop {
+            let offset = (
+                self.rng.gen_range(-d..=d),
+                self.rng.gen_range(-d..=d),
+                self.rng.gen_range(-d..=d),
+            );
+            if offset.0.abs().max(offset.1.abs()).max(offset.2.abs()) == d {
+                return offset;
+            }
+        }
+    }
+
+    // Main exchange logic, returns whether the move was applied
+    fn try_move(&mut self, candidate: Move) -> bool {
         // Random exchange with small probability
-        if rng.gen::<f64>() < PROBABILITY_ANYWAY {
-            self.state.swap(point, neighbor);
-            return;
+        if self.rng.gen::<f64>() < self.config.probability_anyway {
+            candidate.apply(&mut self.state);
+            return true;
         }
 
-        // Check if either point is in a pattern site
-        if let Some((site_idx, site_pos)) = self.find_involved_site(point, neighbor) {
-            self.handle_pattern_exchange(point, neighbor, site_idx, site_pos);
+        // Check if any changed point is in a pattern site (never, for the control run)
+        let involved_site = if self.config.site_guidance {
+            self.find_involved_site(candidate)
         } else {
+            None
+        };
+        if let Some(site_idx) = involved_site {
+            self.handle_pattern_move(candidate, site_idx)
+        } else if self.rng.gen::<f64>() < self.config.probability_exchange {
             // No pattern involved, use normal exchange probability
-            if rng.gen::<f64>() < PROBABILITY_EXCHANGE {
-                self.state.swap(point, neighbor);
-            }
+            candidate.apply(&mut self.state);
+            true
+        } else {
+            false
         }
     }
 
-    // Find if either point is within a pattern site
-    fn find_involved_site(&self, point: Point3, neighbor: Point3) -> Option<(usize, Point2)> {
+    // Find if any point changed by a move is within a pattern site
+    fn find_involved_site(&self, candidate: Move) -> Option<usize> {
         for (idx, site) in self.sites.get_active_sites().iter().enumerate() {
-            let site_shape = site.get_dimensions(&self.bmp);
-
-            if self.is_point_in_site(point, site.position, site_shape)
-                || self.is_point_in_site(neighbor, site.position, site_shape)
-            {
-                return Some((idx, site.position));
+            let in_site = |point| self.is_point_in_site(point, site);
+
+            let involved = match candidate {
+                Move::Swap(point, neighbor) => in_site(point) || in_site(neighbor),
+                Move::Flip(point) => in_site(point),
+            };
+            if involved {
+                return Some(idx);
             }
         }
         None
     }
 
-    // Check if a point is within a site's area (only considers z=0 layer)
-    fn is_point_in_site(
-        &self,
-        point: Point3,
-        site_pos: Point2,
-        site_shape: (usize, usize),
-    ) -> bool {
-        point.2 == 0 && // Only consider points on the first z-layer
-        point.0 >= site_pos.0 && point.0 < site_pos.0 + site_shape.0 &&
-        point.1 >= site_pos.1 && point.1 < site_pos.1 + site_shape.1
+    // Check if a point is within the box covered by a site's pattern
+    fn is_point_in_site(&self, point: Point3, site: &Site) -> bool {
+        let (rows, cols, layers) = site.get_extent(&self.bmp);
+        (site.z..site.z + layers).contains(&point.2)
+            && window_contains(
+                &site.position,
+                (rows, cols),
+                (point.0, point.1),
+                self.config.grid_size.layer(),
+                self.config.wrap(),
+            )
     }
 
-    // Handle exchange when a pattern site is involved
-    fn handle_pattern_exchange(
-        &mut self,
-        point: Point3,
-        neighbor: Point3,
-        site_idx: usize,
-        site_pos: Point2,
-    ) {
-        // Get the bitmap used for this site (custom or default)
-        let site_bitmap = self
+    // Handle a move when a pattern site is involved, returning whether it was kept
+    fn handle_pattern_move(&mut self, candidate: Move, site_idx: usize) -> bool {
+        // Get the pattern used for this site (custom or default)
+        let site = *self
             .sites
             .get_active_sites()
             .get(site_idx)
-            .map(|s| s.get_bitmap(&self.bmp))
             .expect("site index invalid");
-
-        let current_goodness = self.calculate_pattern_goodness(&site_pos, site_bitmap);
-
-        // Temporarily perform the exchange
-        self.state.swap(point, neighbor);
-        let new_goodness = self.calculate_pattern_goodness(&site_pos, site_bitmap);
-
-        if new_goodness > current_goodness {
-            // Exchange improves the pattern, keep it
-            if new_goodness > PATTERN_COMPLETION_THRESHOLD {
-                // Pattern is complete, deactivate the current site and find a new one
-                if let Some(site) = self.sites.get_active_sites_mut().get_mut(site_idx) {
-                    site.deactivate();
+        let origin = site.origin();
+        let pattern = site.get_pattern(&self.bmp);
+
+        let current_mismatches =
+            count_pattern_mismatches(&self.state, origin, pattern, &self.config);
+
+        // Temporarily perform the move
+        candidate.apply(&mut self.state);
+        let new_mismatches = count_pattern_mismatches(&self.state, origin, pattern, &self.config);
+
+        let accepted = match (current_mismatches, new_mismatches) {
+            (Some(current), Some(new)) => match self.config.acceptance {
+                Acceptance::Greedy => new < current,
+                Acceptance::Metropolis => {
+                    // Energy is the number of mismatched bits, so dE <= 0 is always kept
+                    let delta_energy = new as f64 - current as f64;
+                    delta_energy <= 0.0
+                        || self.rng.gen::<f64>() < (-delta_energy / self.temperature).exp()
                 }
-                if let Some(new_site_pos) = self.find_new_site() {
-                    self.sites.add_site(new_site_pos);
+            },
+            // The site does not fit on the grid, so it can never improve
+            _ => false,
+        };
+
+        if !accepted {
+            // Move is rejected, revert it
+            candidate.apply(&mut self.state);
+            return false;
+        }
+
+        let new_goodness = 1.0 - new_mismatches.unwrap_or(0) as f32 / pattern.len() as f32;
+        if new_goodness > self.config.pattern_completion_threshold {
+            // Pattern is complete, deactivate the current site and find a new one
+            if let Some(site) = self.sites.get_active_sites_mut().get_mut(site_idx) {
+                site.deactivate();
+                self.completions.push(Completion {
+                    step: self.step_count,
+                    position: site.position,
+                    z: site.z,
+                    age: self.step_count - site.created_at,
+                });
+            }
+            if let Some(new_site_pos) = self.find_new_site() {
+                let site = self.sites.add_site(new_site_pos);
+                site.z = self.config.site_layer;
+                site.created_at = self.step_count;
+                if self.config.reheat {
+                    self.schedule_start = self.step_count;
+                    self.schedule_temperature = self.config.temperature;
+                    self.temperature = self.config.temperature;
                 }
             }
-        } else {
-            // Exchange doesn't improve the pattern, revert it
-            self.state.swap(point, neighbor);
         }
+        true
     }
 
-    // Calculate how well a pattern matches at a given position
-    fn calculate_pattern_goodness(&self, position: &Point2, bitmap: &Array2<bool>) -> f32 {
-        // Check if the bitmap would fit within the slice at the given coordinates
-        if position.0 + bitmap.dim().0 > GRID_SIZE || position.1 + bitmap.dim().1 > GRID_SIZE {
-            return 0.0;
-        }
-
-        // Extract the 2D slice from the 3D array (z=0 layer)
-        let window = self.state.slice(s![
-            position.0..position.0 + bitmap.dim().0,
-            position.1..position.1 + bitmap.dim().1,
-            0
-        ]);
-
-        // Count matching bits (XOR gives true for mismatches)
-        let mut matches = 0;
-        let total_bits = bitmap.dim().0 * bitmap.dim().1;
-
-        for ((i, j), &bmp_value) in bitmap.indexed_iter() {
-            if window[[i, j]] == bmp_value {
-                matches += 1;
-            }
+    // Calculate how well the main bitmap matches at a position on the site layer
+    fn calculate_pattern_goodness(&self, position: &Point2) -> f32 {
+        let origin = (position.0, position.1, self.config.site_layer);
+        let pattern = self.bmp.view().insert_axis(Axis(2));
+        match count_pattern_mismatches(&self.state, origin, pattern, &self.config) {
+            Some(mismatches) => (pattern.len() - mismatches) as f32 / pattern.len() as f32,
+            None => 0.0,
         }
-
-        matches as f32 / total_bits as f32
     }
 
     // Find a new site location
-    fn find_new_site(&self) -> Option<Point2> {
-        let mut rng = rand::thread_rng();
+    fn find_new_site(&mut self) -> Option<Point2> {
         let mut best_site: Option<Point2> = None;
         let mut best_goodness = 0.0;
 
-        for _ in 0..N_TRIALS {
-            let position = (rng.gen_range(0..GRID_SIZE), rng.gen_range(0..GRID_SIZE));
+        let grid_size = self.config.grid_size;
+        for _ in 0..self.config.n_trials {
+            let position = (
+                self.rng.gen_range(0..grid_size.x),
+                self.rng.gen_range(0..grid_size.y),
+            );
 
             if !self.site_collides_with_existing(position) {
-                let goodness = self.calculate_pattern_goodness(&position, &self.bmp);
+                let goodness = self.calculate_pattern_goodness(&position);
                 if goodness > best_goodness {
                     best_goodness = goodness;
                     best_site = Some(position);
@@ -288,6 +736,23 @@ impl GameState {
 
     // Check if a potential site collides with existing sites
     fn site_collides_with_existing(&self, position: Point2) -> bool {
-        self.sites.collides_with_sites(position, (self.bmp.dim().0, self.bmp.dim().1), &self.bmp)
+        self.sites.collides_with_sites(
+            (position.0, position.1, self.config.site_layer),
+            (self.bmp.dim().0, self.bmp.dim().1, 1),
+            &self.bmp,
+            self.config.grid_size.layer(),
+            self.config.wrap(),
+        )
     }
 }
+
+// Count the bits that differ from a pattern at a given origin, or None if the
+// pattern does not fit on the grid there
+fn count_pattern_mismatches(
+    state: &Array3<bool>,
+    origin: Point3,
+    pattern: ArrayView3<bool>,
+    config: &SimConfig,
+) -> Option<usize> {
+    matching_voxels(&origin, state, pattern, config.wrap()).map(|matches| pattern.len() - matches)
+}
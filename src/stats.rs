@@ -0,0 +1,276 @@
+use serde::Serialize;
+use statrs::distribution::{Binomial, ChiSquared, ContinuousCDF, Discrete, DiscreteCDF, StudentsT};
+
+/// Mean, spread and 95% confidence interval of one metric over many samples
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Summary {
+    pub n: usize,
+    pub mean: f64,
+    /// Unbiased sample variance (0 for fewer than two samples)
+    pub variance: f64,
+    /// Lower and upper bound of the two-sided 95% Student-t interval for the mean
+    pub ci95: (f64, f64),
+}
+
+/// Summarise `samples`, or `None` if there are none
+pub fn summarize(samples: &[f64]) -> Option<Summary> {
+    let n = samples.len();
+    if n == 0 {
+        return None;
+    }
+
+    let mean = mean(samples);
+    let variance = variance(samples);
+    let ci95 = if n < 2 {
+        (mean, mean)
+    } else {
+        let t = StudentsT::new(0.0, 1.0, (n - 1) as f64)
+            .expect("degrees of freedom are positive")
+            .inverse_cdf(0.975);
+        let half_width = t * (variance / n as f64).sqrt();
+        (mean - half_width, mean + half_width)
+    };
+
+    Some(Summary {
+        n,
+        mean,
+        variance,
+        ci95,
+    })
+}
+
+/// Arithmetic mean (NaN for no samples)
+pub fn mean(samples: &[f64]) -> f64 {
+    samples.iter().sum::<f64>() / samples.len() as f64
+}
+
+/// Unbiased sample variance (0 for fewer than two samples)
+pub fn variance(samples: &[f64]) -> f64 {
+    let n = samples.len();
+    if n < 2 {
+        return 0.0;
+    }
+    let mean = mean(samples);
+    samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1) as f64
+}
+
+/// Test statistic and two-sided p-value of a hypothesis test
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct TestResult {
+    pub statistic: f64,
+    pub p_value: f64,
+}
+
+/// Exact binomial test of `successes` out of `trials` against success probability `p`
+///
+/// The statistic is the observed proportion. The two-sided p-value doubles the
+/// smaller tail, capped at 1. With no trials there is no evidence either way,
+/// and the statistic is 0 with a p-value of 1.
+pub fn binomial_test(successes: u64, trials: u64, p: f64) -> TestResult {
+    if trials == 0 {
+        return TestResult {
+            statistic: 0.0,
+            p_value: 1.0,
+        };
+    }
+    let binomial = Binomial::new(p, trials).expect("p is a probability");
+    let lower = binomial.cdf(successes);
+    let upper = upper_tail(&binomial, successes);
+    TestResult {
+        statistic: successes as f64 / trials as f64,
+        p_value: (2.0 * lower.min(upper)).min(1.0),
+    }
+}
+
+/// P(X >= k)
+fn upper_tail(binomial: &Binomial, k: u64) -> f64 {
+    if k == 0 {
+        1.0
+    } else {
+        binomial.sf(k - 1)
+    }
+}
+
+/// Pearson's chi-square test of independence on a 2x2 contingency table
+///
+/// A table with an empty row or column carries no evidence either way and
+/// yields a statistic of 0 and a p-value of 1.
+pub fn chi_square_2x2(table: [[u64; 2]; 2]) -> TestResult {
+    let rows = [table[0][0] + table[0][1], table[1][0] + table[1][1]];
+    let cols = [table[0][0] + table[1][0], table[0][1] + table[1][1]];
+    let total = (rows[0] + rows[1]) as f64;
+    if rows.contains(&0) || cols.contains(&0) {
+        return TestResult {
+            statistic: 0.0,
+            p_value: 1.0,
+        };
+    }
+
+    let mut statistic = 0.0;
+    for (i, row) in table.iter().enumerate() {
+        for (j, &observed) in row.iter().enumerate() {
+            let expected = rows[i] as f64 * cols[j] as f64 / total;
+            statistic += (observed as f64 - expected).powi(2) / expected;
+        }
+    }
+
+    let chi_squared = ChiSquared::new(1.0).expect("one degree of freedom");
+    TestResult {
+        statistic,
+        p_value: chi_squared.sf(statistic),
+    }
+}
+
+/// Two-sample Kolmogorov-Smirnov test
+///
+/// The statistic is the largest distance between the empirical CDFs, the
+/// p-value uses the asymptotic Kolmogorov distribution with the usual
+/// small-sample correction.
+pub fn ks_two_sample(a: &[f64], b: &[f64]) -> TestResult {
+    if a.is_empty() || b.is_empty() {
+        return TestResult {
+            statistic: 0.0,
+            p_value: 1.0,
+        };
+    }
+
+    let mut a = a.to_vec();
+    let mut b = b.to_vec();
+    a.sort_by(f64::total_cmp);
+    b.sort_by(f64::total_cmp);
+
+    let (n_a, n_b) = (a.len() as f64, b.len() as f64);
+    let (mut i, mut j) = (0, 0);
+    let mut distance: f64 = 0.0;
+    while i < a.len() && j < b.len() {
+        // Step past every copy of the smaller value so ties move both CDFs together
+        let x = a[i].min(b[j]);
+        while i < a.len() && a[i] <= x {
+            i += 1;
+        }
+        while j < b.len() && b[j] <= x {
+            j += 1;
+        }
+        distance = distance.max((i as f64 / n_a - j as f64 / n_b).abs());
+    }
+
+    let effective_n = (n_a * n_b / (n_a + n_b)).sqrt();
+    let lambda = (effective_n + 0.12 + 0.11 / effective_n) * distance;
+    TestResult {
+        statistic: distance,
+        p_value: kolmogorov_survival(lambda),
+    }
+}
+
+/// P(K > lambda) for the Kolmogorov distribution
+fn kolmogorov_survival(lambda: f64) -> f64 {
+    if lambda < 1e-3 {
+        return 1.0;
+    }
+    let mut sum = 0.0;
+    let mut sign = 1.0;
+    for j in 1..=100 {
+        let term = sign * (-2.0 * (j as f64 * lambda).powi(2)).exp();
+        sum += term;
+        if term.abs() < 1e-12 {
+            break;
+        }
+        sign = -sign;
+    }
+    (2.0 * sum).clamp(0.0, 1.0)
+}
+
+/// Upper-tail p-value P(M >= matches) for the number of bits of a pattern
+/// matched by chance
+///
+/// Under the null model every cell is independently true with probability
+/// `p`, so `M` is the sum of Binomial(`ones`, p) and Binomial(`zeros`, 1 - p).
+pub fn pattern_match_p_value(matches: u64, ones: u64, zeros: u64, p: f64) -> f64 {
+    let on = Binomial::new(p, ones).expect("p is a probability");
+    let off = Binomial::new(1.0 - p, zeros).expect("p is a probability");
+
+    let mut tail = 0.0;
+    for k in 0..=ones {
+        let needed = matches.saturating_sub(k);
+        if needed > zeros {
+            continue;
+        }
+        tail += on.pmf(k) * upper_tail(&off, needed);
+    }
+    tail.min(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize() {
+        let summary = summarize(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]).unwrap();
+        assert_eq!(summary.n, 8);
+        assert_eq!(summary.mean, 5.0);
+        assert!((summary.variance - 32.0 / 7.0).abs() < 1e-12);
+        // t(0.975, 7) = 2.364624...
+        let half_width = 2.364624 * (32.0f64 / 7.0 / 8.0).sqrt();
+        assert!((summary.ci95.0 - (5.0 - half_width)).abs() < 1e-4);
+        assert!((summary.ci95.1 - (5.0 + half_width)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_summarize_degenerate() {
+        assert!(summarize(&[]).is_none());
+        let single = summarize(&[3.0]).unwrap();
+        assert_eq!(single.variance, 0.0);
+        assert_eq!(single.ci95, (3.0, 3.0));
+    }
+
+    #[test]
+    fn test_binomial_test() {
+        // 9 heads in 10 fair flips: P(X >= 9) = 11/1024
+        let result = binomial_test(9, 10, 0.5);
+        assert_eq!(result.statistic, 0.9);
+        assert!((result.p_value - 22.0 / 1024.0).abs() < 1e-12);
+        assert_eq!(binomial_test(5, 10, 0.5).p_value, 1.0);
+    }
+
+    #[test]
+    fn test_binomial_test_without_trials() {
+        let result = binomial_test(0, 0, 0.3);
+        assert_eq!(result.statistic, 0.0);
+        assert_eq!(result.p_value, 1.0);
+    }
+
+    #[test]
+    fn test_chi_square_2x2() {
+        let result = chi_square_2x2([[10, 20], [30, 40]]);
+        // Expected counts 12, 18, 28, 42
+        let statistic = 4.0 / 12.0 + 4.0 / 18.0 + 4.0 / 28.0 + 4.0 / 42.0;
+        assert!((result.statistic - statistic).abs() < 1e-12);
+        assert!((result.p_value - 0.3730).abs() < 1e-3);
+        assert_eq!(chi_square_2x2([[0, 0], [3, 4]]).p_value, 1.0);
+    }
+
+    #[test]
+    fn test_ks_two_sample() {
+        let a: Vec<f64> = (0..100).map(|i| i as f64).collect();
+        let same = ks_two_sample(&a, &a);
+        assert_eq!(same.statistic, 0.0);
+        assert_eq!(same.p_value, 1.0);
+
+        let shifted: Vec<f64> = a.iter().map(|x| x + 50.0).collect();
+        let result = ks_two_sample(&a, &shifted);
+        assert!((result.statistic - 0.5).abs() < 1e-12);
+        assert!(result.p_value < 1e-8);
+    }
+
+    #[test]
+    fn test_pattern_match_p_value() {
+        // A single true bit at p = 0.25 is matched by chance a quarter of the time
+        assert!((pattern_match_p_value(1, 1, 0, 0.25) - 0.25).abs() < 1e-12);
+        // A single false bit is matched three quarters of the time
+        assert!((pattern_match_p_value(1, 0, 1, 0.25) - 0.75).abs() < 1e-12);
+        assert_eq!(pattern_match_p_value(0, 3, 3, 0.25), 1.0);
+        // Matching all of one true and one false bit: 0.25 * 0.75
+        assert!((pattern_match_p_value(2, 1, 1, 0.25) - 0.1875).abs() < 1e-12);
+    }
+}
@@ -1,6 +1,9 @@
 #[cfg(test)]
 mod tests {
-    use crate::bitmap_loader::{load_bitmap_from_bmp, load_bitmaps_from_directory};
+    use crate::bitmap_loader::{
+        builtin_bitmaps, load_bitmap_from_bmp, load_bitmap_library, load_bitmaps_from_directory,
+        load_bitmaps_from_files,
+    };
 
     #[test]
     fn test_load_main_bitmap() {
@@ -8,7 +11,7 @@ mod tests {
         assert!(result.is_ok(), "Should be able to load main_bitmap.bmp");
 
         let bitmap = result.unwrap();
-        assert_eq!(bitmap.dim(), (20, 20), "Main bitmap should be 20x20");
+        assert_eq!(bitmap.dim(), (21, 21), "Main bitmap should be 21x21");
     }
 
     #[test]
@@ -17,7 +20,7 @@ mod tests {
         assert!(result.is_ok(), "Should be able to load player_bitmap.bmp");
 
         let bitmap = result.unwrap();
-        assert_eq!(bitmap.dim(), (3, 3), "Player bitmap should be 3x3");
+        assert_eq!(bitmap.dim(), (15, 15), "Player bitmap should be 15x15");
     }
 
     #[test]
@@ -44,21 +47,46 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_bitmap_library() {
+        let library = load_bitmap_library("bitmaps");
+        let names: Vec<&str> = library.iter().map(|b| b.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "diagonal",
+                "hollow_square",
+                "square",
+                "cross",
+                "small_square",
+                "small_diagonal",
+                "small_hollow_square"
+            ]
+        );
+
+        let bitmaps: Vec<_> = library.into_iter().map(|b| b.bitmap).collect();
+        assert_eq!(load_bitmaps_from_files(), bitmaps);
+
+        // Without the directory only the built-ins remain
+        assert_eq!(load_bitmap_library("no_such_directory"), builtin_bitmaps());
+    }
+
     #[test]
     fn test_bitmap_content() {
         let bitmap = load_bitmap_from_bmp("player_bitmap.bmp").unwrap();
 
-        // The player bitmap should be a cross pattern
-        assert!(bitmap[[1, 1]], "Center should be true");
-        assert!(bitmap[[0, 1]], "Left center should be true");
-        assert!(bitmap[[2, 1]], "Right center should be true");
-        assert!(bitmap[[1, 0]], "Top center should be true");
-        assert!(bitmap[[1, 2]], "Bottom center should be true");
+        // The player bitmap should be a smiley face
+        assert!(bitmap[[0, 7]], "Top of the outline should be true");
+        assert!(bitmap[[7, 0]], "Left of the outline should be true");
+        assert!(bitmap[[5, 5]], "Left eye should be true");
+        assert!(bitmap[[5, 9]], "Right eye should be true");
+        assert!(bitmap[[9, 7]], "Mouth should be true");
+        assert!(!bitmap[[7, 7]], "Center should be false");
 
         // Corners should be false
         assert!(!bitmap[[0, 0]], "Top-left corner should be false");
-        assert!(!bitmap[[2, 0]], "Top-right corner should be false");
-        assert!(!bitmap[[0, 2]], "Bottom-left corner should be false");
-        assert!(!bitmap[[2, 2]], "Bottom-right corner should be false");
+        assert!(!bitmap[[14, 0]], "Bottom-left corner should be false");
+        assert!(!bitmap[[0, 14]], "Top-right corner should be false");
+        assert!(!bitmap[[14, 14]], "Bottom-right corner should be false");
     }
 }
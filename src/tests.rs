@@ -1,6 +1,10 @@
-use super::*;
-use site::SiteManager;
-
+use crate::bitmap_loader::load_bitmap_from_bmp;
+use crate::config::{Acceptance, Boundary, Dynamics, GridSize, Neighborhood, Schedule, SimConfig};
+use crate::goodness::{goodness, goodness_3d, xor, NO_WRAP};
+use crate::player::{Direction, FAST_MOVE};
+use crate::site::SiteManager;
+use crate::state::{GameState, PlacementError, Point2, Point3};
+use ndarray::*;
 
 #[test]
 fn test_xor() {
@@ -20,7 +24,7 @@ fn test_goodness() {
     ];
     let side: Array3<bool> = Array3::from_elem((5, 5, 1), true);
     let cords: Point2 = (0, 0);
-    let goodness = goodness(&cords, &side, &bmp);
+    let goodness = goodness(&cords, &side, &bmp, NO_WRAP);
     assert_eq!(
         goodness, 0.36,
         "Goodness should be 0.36 for the given bitmap and side"
@@ -31,16 +35,529 @@ fn test_collides_true() {
     let mut sites = SiteManager::new();
     sites.add_site((2, 1));
     let site_shape: (usize, usize) = (3, 3);
-    let s: Point2 = (0, 0);
+    let s: Point3 = (0, 0, 0);
     let bmp: Array2<bool> = Array2::from_elem(site_shape, false);
-    assert_eq!(sites.collides_with_sites(s, site_shape, &bmp), true);
+    assert!(sites.collides_with_sites(s, (3, 3, 1), &bmp, (10, 10), NO_WRAP));
 }
 #[test]
 fn test_collides_false() {
     let mut sites = SiteManager::new();
     sites.add_site((3, 1));
     let site_shape: (usize, usize) = (3, 3);
-    let s: Point2 = (0, 0);
+    let s: Point3 = (0, 0, 0);
+    let bmp: Array2<bool> = Array2::from_elem(site_shape, false);
+    assert!(!sites.collides_with_sites(s, (3, 3, 1), &bmp, (10, 10), NO_WRAP));
+}
+#[test]
+fn test_collides_only_on_shared_layers() {
+    let mut sites = SiteManager::new();
+    sites.add_volume_site((2, 1, 2), Array3::from_elem((3, 3, 2), true));
+    let bmp: Array2<bool> = Array2::from_elem((3, 3), false);
+    assert!(!sites.collides_with_sites((0, 0, 0), (3, 3, 2), &bmp, (10, 10), NO_WRAP));
+    assert!(sites.collides_with_sites((0, 0, 1), (3, 3, 2), &bmp, (10, 10), NO_WRAP));
+    assert!(sites.collides_with_sites((0, 0, 3), (3, 3, 1), &bmp, (10, 10), NO_WRAP));
+    assert!(!sites.collides_with_sites((0, 0, 4), (3, 3, 1), &bmp, (10, 10), NO_WRAP));
+}
+#[test]
+fn test_collides_across_periodic_edge() {
+    let mut sites = SiteManager::new();
+    sites.add_site((8, 8));
+    let site_shape: (usize, usize) = (3, 3);
+    let s: Point3 = (0, 0, 0);
     let bmp: Array2<bool> = Array2::from_elem(site_shape, false);
-    assert_eq!(sites.collides_with_sites(s, site_shape, &bmp), false);
-}
\ No newline at end of file
+    assert!(!sites.collides_with_sites(s, (3, 3, 1), &bmp, (10, 10), NO_WRAP));
+    assert!(sites.collides_with_sites(s, (3, 3, 1), &bmp, (10, 10), (true, true)));
+    assert!(!sites.collides_with_sites(s, (3, 3, 1), &bmp, (10, 10), (true, false)));
+}
+#[test]
+fn test_find_and_remove_site_at() {
+    let mut sites = SiteManager::new();
+    sites.add_site((2, 1));
+    sites.add_site((5, 5));
+    // Only a site placed exactly there is found, not one covering the point
+    assert!(sites.find_site_at((3, 2)).is_none());
+    assert_eq!(sites.find_site_at((5, 5)).unwrap().position, (5, 5));
+    sites.find_site_at_mut((2, 1)).unwrap().deactivate();
+    assert!(!sites.find_site_at((2, 1)).unwrap().is_active);
+    assert_eq!(sites.remove_site_at((2, 1)).unwrap().position, (2, 1));
+    assert!(sites.remove_site_at((2, 1)).is_none());
+    assert_eq!(sites.total_count(), 1);
+}
+#[test]
+fn test_goodness_wraps_across_periodic_edge() {
+    let bmp: Array2<bool> = array![[true, false], [false, true]];
+    let mut side: Array3<bool> = Array3::from_elem((4, 4, 1), false);
+    side[[3, 3, 0]] = true;
+    side[[0, 0, 0]] = true;
+    let cords: Point2 = (3, 3);
+    assert_eq!(goodness(&cords, &side, &bmp, NO_WRAP), 0.0);
+    assert_eq!(goodness(&cords, &side, &bmp, (true, true)), 1.0);
+}
+fn seeded_game_state(seed: u64) -> GameState {
+    let bmp = load_bitmap_from_bmp("main_bitmap.bmp").unwrap();
+    let player_bmp = load_bitmap_from_bmp("player_bitmap.bmp").unwrap();
+    GameState::from_bitmaps(SimConfig::default(), bmp, player_bmp, seed).unwrap()
+}
+
+// A run with no automatic sites whose bitmaps are both all true
+fn site_free_game_state(bitmap_dim: (usize, usize), grid_size: GridSize) -> GameState {
+    let bmp = Array2::from_elem(bitmap_dim, true);
+    let config = SimConfig {
+        grid_size,
+        n_sites: 0,
+        ..SimConfig::default()
+    };
+    GameState::from_bitmaps(config, bmp.clone(), bmp, 4).unwrap()
+}
+
+// The 8x8x8 run with two sites for a diagonal 2x2 bitmap that the dynamics
+// tests share, seeded with `seed` and with `configure` applied to its config
+pub(crate) fn small_game_state(seed: u64, configure: impl FnOnce(&mut SimConfig)) -> GameState {
+    let bmp = array![[true, false], [false, true]];
+    let mut config = SimConfig {
+        grid_size: GridSize::cube(8),
+        n_sites: 2,
+        ..SimConfig::default()
+    };
+    configure(&mut config);
+    GameState::from_bitmaps(config, bmp.clone(), bmp, seed).unwrap()
+}
+
+fn site_history(game_state: &GameState) -> Vec<(Point2, bool)> {
+    game_state
+        .sites
+        .get_all_sites()
+        .iter()
+        .map(|site| (site.position, site.is_active))
+        .collect()
+}
+
+// Step until the run has `completions` completions, failing if that takes
+// more than `max_steps` steps
+fn step_until_completions(game_state: &mut GameState, completions: usize, max_steps: usize) {
+    for _ in 0..max_steps {
+        if game_state.get_completions().len() >= completions {
+            break;
+        }
+        game_state.step();
+    }
+    assert!(
+        game_state.get_completions().len() >= completions,
+        "only {} of {} completions after {} steps",
+        game_state.get_completions().len(),
+        completions,
+        max_steps
+    );
+}
+
+#[test]
+fn test_same_seed_reproduces_run() {
+    let mut a = seeded_game_state(42);
+    let mut b = seeded_game_state(42);
+    assert_eq!(a.state, b.state, "initial grids should match");
+    assert_eq!(
+        site_history(&a),
+        site_history(&b),
+        "initial sites should match"
+    );
+
+    for _ in 0..20_000 {
+        a.step();
+        b.step();
+    }
+    assert_eq!(a.state, b.state, "grids should match after stepping");
+    assert_eq!(
+        site_history(&a),
+        site_history(&b),
+        "site histories should match"
+    );
+    assert_eq!(a.get_seed(), 42);
+}
+
+#[test]
+fn test_different_seeds_diverge() {
+    let a = seeded_game_state(1);
+    let b = seeded_game_state(2);
+    assert_ne!(a.state, b.state);
+}
+
+// Mean goodness of the one site in a small grid after `steps` steps under `acceptance`
+fn site_goodness_after(acceptance: Acceptance, temperature: f64, steps: usize) -> f32 {
+    let bmp = Array2::from_shape_fn((4, 4), |(i, j)| (i + j) % 2 == 0);
+    let config = SimConfig {
+        grid_size: GridSize::cube(10),
+        n_sites: 1,
+        // Never complete, so the site stays put
+        pattern_completion_threshold: 1.0,
+        probability_anyway: 0.0,
+        acceptance,
+        temperature,
+        ..SimConfig::default()
+    };
+    let mut game_state = GameState::from_bitmaps(config, bmp.clone(), bmp, 3).unwrap();
+    for _ in 0..steps {
+        game_state.step();
+    }
+    let site = &game_state.sites.get_all_sites()[0];
+    goodness(&site.position, &game_state.state, &game_state.bmp, NO_WRAP)
+}
+
+#[test]
+fn test_metropolis_temperature_controls_determination() {
+    let greedy = site_goodness_after(Acceptance::Greedy, 1.0, 50_000);
+    let cold = site_goodness_after(Acceptance::Metropolis, 0.05, 50_000);
+    let hot = site_goodness_after(Acceptance::Metropolis, 1e6, 50_000);
+    assert!(greedy > 0.9, "greedy goodness {}", greedy);
+    assert!(cold > 0.9, "cold goodness {}", cold);
+    // At a very high temperature every swap is kept, as if there were no site
+    assert!(hot < cold, "hot goodness {} vs cold {}", hot, cold);
+}
+
+#[test]
+fn test_move_counters_follow_exchange_probability() {
+    let counts = |probability_exchange: f64| {
+        let mut game_state = seeded_game_state(5);
+        game_state.config.site_guidance = false;
+        game_state.config.probability_anyway = 0.0;
+        game_state.config.probability_exchange = probability_exchange;
+        for _ in 0..2_000 {
+            game_state.step();
+        }
+        (
+            game_state.get_proposed_moves(),
+            game_state.get_accepted_moves(),
+        )
+    };
+
+    let (proposed, accepted) = counts(1.0);
+    assert!(proposed > 0 && proposed <= 2_000);
+    assert_eq!(accepted, proposed);
+    let (proposed, accepted) = counts(0.0);
+    assert!(proposed > 0);
+    assert_eq!(accepted, 0);
+}
+
+#[test]
+fn test_temperature_is_exposed() {
+    let mut game_state = seeded_game_state(1);
+    assert_eq!(game_state.get_temperature(), game_state.config.temperature);
+    game_state.set_temperature(0.25);
+    assert_eq!(game_state.get_temperature(), 0.25);
+}
+
+#[test]
+fn test_schedule_cools_and_reheats_on_new_site() {
+    let mut game_state = small_game_state(5, |config| {
+        config.acceptance = Acceptance::Metropolis;
+        config.temperature = 2.0;
+        config.schedule = Schedule::Exponential;
+        config.cooling_rate = 0.99;
+        config.reheat = true;
+    });
+
+    for _ in 0..10 {
+        game_state.step();
+    }
+    assert!(game_state.get_temperature() < 2.0);
+
+    // A manual override restarts the schedule but reheating returns to the
+    // configured temperature
+    game_state.set_temperature(5.0);
+    game_state.step();
+    assert!(game_state.get_temperature() > 4.9);
+    assert_eq!(game_state.config.temperature, 2.0);
+
+    step_until_completions(&mut game_state, 1, 1_000_000);
+    assert_eq!(game_state.get_temperature(), 2.0);
+}
+
+#[test]
+fn test_only_glauber_dynamics_changes_the_number_of_true_bits() {
+    let run = |dynamics| {
+        let mut game_state = small_game_state(2, |config| config.dynamics = dynamics);
+        let initial = game_state.fill_ratio();
+        for _ in 0..20_000 {
+            game_state.step();
+        }
+        (initial, game_state)
+    };
+
+    let (initial, kawasaki) = run(Dynamics::Kawasaki);
+    assert_eq!(kawasaki.fill_ratio(), initial);
+
+    let (initial, glauber) = run(Dynamics::Glauber);
+    assert_ne!(glauber.fill_ratio(), initial);
+    assert!(!glauber.get_completions().is_empty());
+}
+
+#[test]
+fn test_neighborhoods_mix_within_their_reach() {
+    let run = |neighborhood| {
+        let mut game_state = small_game_state(4, |config| {
+            config.neighborhood = neighborhood;
+            config.max_jump = 4;
+        });
+        let initial = game_state.state.clone();
+        for _ in 0..20_000 {
+            game_state.step();
+        }
+        (initial, game_state)
+    };
+    let layer_counts = |state: &Array3<bool>| -> Vec<usize> {
+        state
+            .axis_iter(Axis(2))
+            .map(|layer| layer.iter().filter(|&&b| b).count())
+            .collect()
+    };
+
+    // In-plane swaps never move a bit to another layer
+    for neighborhood in [Neighborhood::Plane4, Neighborhood::Plane8] {
+        let (initial, game_state) = run(neighborhood);
+        assert_ne!(initial, game_state.state, "{:?}", neighborhood);
+        assert_eq!(layer_counts(&initial), layer_counts(&game_state.state));
+    }
+
+    for neighborhood in [Neighborhood::Moore, Neighborhood::LongRange] {
+        let (initial, game_state) = run(neighborhood);
+        assert_ne!(layer_counts(&initial), layer_counts(&game_state.state));
+        assert_eq!(
+            initial.iter().filter(|&&b| b).count(),
+            game_state.state.iter().filter(|&&b| b).count()
+        );
+    }
+}
+
+#[test]
+fn test_periodic_boundaries_keep_every_neighbor() {
+    let mut game_state = small_game_state(8, |config| {
+        config.grid_size = GridSize::cube(6);
+        config.n_sites = 3;
+        config.boundary_x = Boundary::Periodic;
+        config.boundary_y = Boundary::Periodic;
+        config.boundary_z = Boundary::Reflecting;
+    });
+
+    // A corner cell keeps all six von Neumann neighbors: across the periodic
+    // edges they wrap around, at the reflecting one the mirrored cell counts
+    let corner = (0, 0, 5);
+    let mut neighbors = game_state.neighbors(corner);
+    neighbors.sort();
+    assert_eq!(
+        neighbors,
+        vec![
+            (0, 0, 4),
+            (0, 0, 4),
+            (0, 1, 5),
+            (0, 5, 5),
+            (1, 0, 5),
+            (5, 0, 5)
+        ]
+    );
+    // Walls drop the neighbors across them
+    game_state.config.boundary_x = Boundary::Wall;
+    game_state.config.boundary_z = Boundary::Wall;
+    assert_eq!(game_state.neighbors(corner).len(), 4);
+    game_state.config.boundary_x = Boundary::Periodic;
+    game_state.config.boundary_z = Boundary::Reflecting;
+
+    let initial = game_state.fill_ratio();
+    step_until_completions(&mut game_state, 5, 1_000_000);
+    assert_eq!(game_state.fill_ratio(), initial);
+}
+
+#[test]
+fn test_flat_non_cubic_grid_with_non_square_bitmap() {
+    let bmp = array![[true, false, true], [false, true, false]];
+    let config = SimConfig {
+        grid_size: GridSize { x: 12, y: 7, z: 1 },
+        n_sites: 2,
+        ..SimConfig::default()
+    };
+    let mut game_state = GameState::from_bitmaps(config, bmp.clone(), bmp, 6).unwrap();
+    assert_eq!(game_state.state.dim(), (12, 7, 1));
+    assert_eq!(game_state.get_render_slice().dim(), (12, 7));
+
+    step_until_completions(&mut game_state, 3, 1_000_000);
+    for site in game_state.sites.get_all_sites() {
+        assert!(site.position.0 + 2 <= 12 && site.position.1 + 3 <= 7);
+    }
+
+    // The player stops where its 2x3 bitmap still fits
+    for _ in 0..20 {
+        game_state.move_player(Direction::DownRight, 1);
+    }
+    assert_eq!(game_state.player.position, (10, 4));
+}
+
+#[test]
+fn test_player_moves_keep_the_bitmap_on_the_grid() {
+    let mut game_state = site_free_game_state((3, 4), GridSize { x: 10, y: 12, z: 2 });
+
+    game_state.move_player(Direction::Right, FAST_MOVE);
+    assert_eq!(game_state.player.position, (0, 5));
+    game_state.move_player(Direction::UpRight, FAST_MOVE);
+    assert_eq!(game_state.player.position, (0, 8));
+    game_state.move_player(Direction::DownLeft, 2);
+    assert_eq!(game_state.player.position, (2, 6));
+    game_state.move_player(Direction::Down, 100);
+    assert_eq!(game_state.player.position, (7, 6));
+
+    // Across a periodic axis the bitmap may wrap instead
+    game_state.config.boundary_x = Boundary::Periodic;
+    game_state.move_player(Direction::Down, 4);
+    assert_eq!(game_state.player.position, (1, 6));
+    game_state.move_player(Direction::Up, 2);
+    assert_eq!(game_state.player.position, (9, 6));
+}
+
+#[test]
+fn test_clamping_keeps_a_larger_bitmap_on_the_grid() {
+    let mut game_state = site_free_game_state((3, 4), GridSize { x: 10, y: 12, z: 2 });
+    game_state.move_player(Direction::DownRight, 100);
+    assert_eq!(game_state.player.position, (7, 8));
+
+    game_state.player.bitmap = Array2::from_elem((5, 6), true);
+    game_state.clamp_player();
+    assert_eq!(game_state.player.position, (5, 6));
+    // A bitmap that already fits stays where it is
+    game_state.player.bitmap = Array2::from_elem((1, 1), true);
+    game_state.clamp_player();
+    assert_eq!(game_state.player.position, (5, 6));
+}
+
+#[test]
+fn test_volume_site_forms_below_the_display_layer() {
+    let mut game_state = small_game_state(12, |config| {
+        config.grid_size = GridSize::cube(6);
+        config.n_sites = 0;
+    });
+    let volume = Array3::from_shape_fn((2, 2, 3), |(i, j, k)| (i + j + k) % 2 == 0);
+    game_state.add_volume_site((1, 2, 2), volume.clone());
+    assert!(!game_state.sites.get_all_sites()[0].is_on_display_layer(&game_state.bmp));
+
+    step_until_completions(&mut game_state, 1, 1_000_000);
+    let completion = &game_state.get_completions()[0];
+    assert_eq!((completion.position, completion.z), ((1, 2), 2));
+    assert_eq!(
+        goodness_3d(&(1, 2, 2), &game_state.state, volume.view(), NO_WRAP),
+        1.0
+    );
+}
+
+#[test]
+fn test_automatic_sites_use_the_site_layer() {
+    let mut game_state = small_game_state(13, |config| config.site_layer = 3);
+    step_until_completions(&mut game_state, 3, 1_000_000);
+    assert!(game_state
+        .sites
+        .get_all_sites()
+        .iter()
+        .all(|site| site.z == 3));
+    assert!(game_state.get_completions().iter().all(|c| c.z == 3));
+
+    let config = SimConfig {
+        grid_size: GridSize { x: 8, y: 8, z: 1 },
+        site_layer: 1,
+        ..SimConfig::default()
+    };
+    assert!(config.validate().is_err());
+}
+
+#[test]
+fn test_forced_sites_use_the_site_layer() {
+    let mut game_state = site_free_game_state((3, 3), GridSize::cube(10));
+    game_state.config.site_layer = 2;
+    game_state.sites.add_site((0, 0)).z = 2;
+
+    // The automatic site on layer 2 blocks the player
+    game_state.player.position = (1, 1);
+    assert_eq!(game_state.player_origin(), (1, 1, 2));
+    assert_eq!(game_state.force_site(), Err(PlacementError::Overlaps));
+    game_state.player.position = (5, 5);
+    assert_eq!(game_state.force_site(), Ok(()));
+    assert_eq!(game_state.sites.get_all_sites()[1].z, 2);
+}
+
+#[test]
+fn test_force_site_refuses_overlaps_and_grid_edges() {
+    let mut game_state = site_free_game_state((3, 3), GridSize::cube(10));
+
+    assert_eq!(game_state.force_site(), Ok(()));
+    assert_eq!(game_state.force_site(), Err(PlacementError::Overlaps));
+    game_state.player.position = (3, 0);
+    assert_eq!(game_state.force_site(), Ok(()));
+
+    game_state.player.position = (8, 5);
+    assert_eq!(game_state.check_player_site(), Err(PlacementError::OffGrid));
+    game_state.config.boundary_x = Boundary::Periodic;
+    assert_eq!(game_state.check_player_site(), Ok(()));
+    assert_eq!(game_state.sites.total_count(), 2);
+}
+
+#[test]
+fn test_sites_can_be_selected_moved_retargeted_and_removed() {
+    let mut game_state = site_free_game_state((3, 3), GridSize::cube(12));
+    game_state.sites.add_site((0, 0));
+    game_state.sites.add_site((6, 6));
+
+    game_state.player.position = (7, 8);
+    assert_eq!(game_state.find_site_under_player(), Some(1));
+    game_state.player.position = (4, 4);
+    assert_eq!(game_state.find_site_under_player(), None);
+
+    // Site 1 may not be dragged onto site 0, or off the grid
+    assert_eq!(
+        game_state.move_site(1, (2, 2)),
+        Err(PlacementError::Overlaps)
+    );
+    assert_eq!(
+        game_state.move_site(1, (10, 6)),
+        Err(PlacementError::OffGrid)
+    );
+    assert_eq!(game_state.move_site(1, (3, 3)), Ok(()));
+    assert_eq!(game_state.sites.get_all_sites()[1].position, (3, 3));
+
+    // A completed site does not block, but reactivating it checks again
+    game_state.sites.get_all_sites_mut()[0].deactivate();
+    assert_eq!(game_state.move_site(1, (1, 1)), Ok(()));
+    assert_eq!(game_state.reactivate_site(0), Err(PlacementError::Overlaps));
+    assert_eq!(game_state.move_site(1, (6, 6)), Ok(()));
+    assert_eq!(game_state.reactivate_site(0), Ok(()));
+    assert!(game_state.sites.get_all_sites()[0].is_active);
+    assert_eq!(
+        game_state.reactivate_site(0),
+        Err(PlacementError::AlreadyActive)
+    );
+
+    let target = array![[true, false], [false, true]];
+    assert_eq!(game_state.retarget_site(1, target.clone()), Ok(()));
+    let site = &game_state.sites.get_all_sites()[1];
+    assert_eq!(site.get_dimensions(&game_state.bmp), (2, 2));
+    assert_eq!(
+        site.get_pattern(&game_state.bmp).index_axis(Axis(2), 0),
+        target
+    );
+
+    let removed = game_state.remove_site(0);
+    assert_eq!(removed.position, (0, 0));
+    assert_eq!(game_state.sites.total_count(), 1);
+}
+
+#[test]
+fn test_grid_region_reads_the_site_layer() {
+    let mut game_state = site_free_game_state((2, 2), GridSize::cube(6));
+    game_state.config.site_layer = 1;
+    game_state.state.fill(false);
+    game_state.state[[5, 0, 1]] = true;
+    game_state.state[[4, 4, 0]] = true;
+
+    let region = game_state.grid_region((4, 4), (3, 3));
+    assert_eq!(region, Array2::from_elem((3, 3), false));
+
+    // Past a wall the region reads empty; across a periodic edge it wraps
+    let region = game_state.grid_region((5, 5), (2, 2));
+    assert!(!region.iter().any(|&cell| cell));
+    game_state.config.boundary_y = Boundary::Periodic;
+    let region = game_state.grid_region((5, 5), (2, 2));
+    assert_eq!(region, array![[false, true], [false, false]]);
+}
@@ -0,0 +1,271 @@
+use super::{shade, App};
+use crate::player::Direction;
+use crate::state::Point2;
+use cursive::event::{Event, EventResult, Key};
+use cursive::theme::{Color, ColorStyle};
+use cursive::traits::{Nameable, Resizable};
+use cursive::views::{Canvas, Dialog, EditView, LinearLayout, NamedView, OnEventView, TextView};
+use cursive::{Cursive, Printer, Vec2};
+use ndarray::*;
+use std::error::Error;
+
+// Names of the editor canvas and the name field of the save prompt
+const EDITOR: &str = "editor";
+const NAME: &str = "bitmap name";
+
+/// A bitmap being drawn in the editor, with the cell its cursor is on
+pub struct Editor {
+    bitmap: Array2<bool>,
+    cursor: Point2,
+    // Largest size that still fits the site layer
+    max_dim: (usize, usize),
+}
+
+impl Editor {
+    pub fn new(bitmap: Array2<bool>, max_dim: (usize, usize)) -> Editor {
+        let mut editor = Editor {
+            bitmap: Array2::from_elem((1, 1), false),
+            cursor: (0, 0),
+            max_dim,
+        };
+        editor.load(bitmap);
+        editor
+    }
+
+    pub fn bitmap(&self) -> &Array2<bool> {
+        &self.bitmap
+    }
+
+    /// Start over from `bitmap`, cropped to the largest size that fits
+    pub fn load(&mut self, bitmap: Array2<bool>) {
+        let (rows, cols) = bitmap.dim();
+        if rows == 0 || cols == 0 {
+            return;
+        }
+        let (rows, cols) = (rows.min(self.max_dim.0), cols.min(self.max_dim.1));
+        self.bitmap = bitmap.slice(s![..rows, ..cols]).to_owned();
+        self.clamp_cursor();
+    }
+
+    pub fn move_cursor(&mut self, direction: Direction) {
+        let (dr, dc) = direction.offset();
+        let (r, c) = self.cursor;
+        self.cursor = (r.saturating_add_signed(dr), c.saturating_add_signed(dc));
+        self.clamp_cursor();
+    }
+
+    pub fn toggle(&mut self) {
+        let cell = &mut self.bitmap[self.cursor];
+        *cell = !*cell;
+    }
+
+    pub fn clear(&mut self) {
+        self.bitmap.fill(false);
+    }
+
+    /// Add or drop a row or column at the bottom or right edge, keeping the
+    /// other pixels where they are; Down and Right grow, Up and Left shrink
+    pub fn resize(&mut self, direction: Direction) {
+        let (dr, dc) = direction.offset();
+        let (rows, cols) = self.bitmap.dim();
+        let dim = (
+            rows.saturating_add_signed(dr).clamp(1, self.max_dim.0),
+            cols.saturating_add_signed(dc).clamp(1, self.max_dim.1),
+        );
+        let old = &self.bitmap;
+        self.bitmap = Array2::from_shape_fn(dim, |pos| old.get(pos).copied().unwrap_or(false));
+        self.clamp_cursor();
+    }
+
+    fn clamp_cursor(&mut self) {
+        let (rows, cols) = self.bitmap.dim();
+        self.cursor = (self.cursor.0.min(rows - 1), self.cursor.1.min(cols - 1));
+    }
+
+    fn status(&self) -> String {
+        let (rows, cols) = self.bitmap.dim();
+        format!(
+            "{}x{}, cursor {},{}",
+            rows, cols, self.cursor.0, self.cursor.1
+        )
+    }
+}
+
+/// Open the editor over the main screen, starting from the player's bitmap
+pub fn open(s: &mut Cursive) {
+    let Some(editor) = s.with_user_data(|app: &mut App| {
+        Editor::new(
+            app.game_state.player.bitmap.clone(),
+            app.game_state.config.grid_size.layer(),
+        )
+    }) else {
+        return;
+    };
+    let canvas = Canvas::new(editor)
+        .with_draw(draw)
+        .with_on_event(on_event)
+        .with_required_size(|editor, _| {
+            let (rows, cols) = editor.bitmap.dim();
+            Vec2::new(cols.max(editor.status().len()), rows + 2)
+        })
+        .with_take_focus(|_, _| Ok(EventResult::Consumed(None)));
+    let dialog = Dialog::around(
+        LinearLayout::vertical()
+            .child(NamedView::new(EDITOR, canvas))
+            .child(TextView::new(
+                "arrows move, space toggles, shift+arrows resize, c clears",
+            )),
+    )
+    .title("bitmap editor")
+    .button("Player", |s| {
+        import(s, |app, _| app.game_state.player.bitmap.clone());
+    })
+    .button("Grid", |s| {
+        import(s, |app, dim| {
+            app.game_state
+                .grid_region(app.game_state.player.position, dim)
+        });
+    })
+    .button("Save", prompt_save)
+    .dismiss_button("Close");
+    s.add_layer(OnEventView::new(dialog).on_event(Key::Esc, |s| {
+        s.pop_layer();
+    }));
+}
+
+// The bitmap with the cursor drawn like the player's, and its size below
+fn draw(editor: &Editor, printer: &Printer) {
+    let cursor_style = ColorStyle::new(Color::Rgb(255, 255, 255), Color::Rgb(150, 0, 150));
+    for ((r, c), &value) in editor.bitmap.indexed_iter() {
+        let ch = shade(if value { 1.0 } else { 0.0 });
+        if (r, c) == editor.cursor {
+            printer.with_color(cursor_style, |printer| printer.print((c, r), ch));
+        } else {
+            printer.print((c, r), ch);
+        }
+    }
+    printer.print((0, editor.bitmap.nrows() + 1), &editor.status());
+}
+
+fn on_event(editor: &mut Editor, event: Event) -> EventResult {
+    let arrow = |key| match key {
+        Key::Up => Some(Direction::Up),
+        Key::Down => Some(Direction::Down),
+        Key::Left => Some(Direction::Left),
+        Key::Right => Some(Direction::Right),
+        _ => None,
+    };
+    match event {
+        Event::Key(key) if arrow(key).is_some() => {
+            editor.move_cursor(arrow(key).expect("checked above"));
+        }
+        Event::Shift(key) if arrow(key).is_some() => {
+            editor.resize(arrow(key).expect("checked above"));
+        }
+        Event::Char(' ') => editor.toggle(),
+        Event::Char('c') => editor.clear(),
+        _ => return EventResult::Ignored,
+    }
+    EventResult::Consumed(None)
+}
+
+// Replace the editor's bitmap with one taken from the app, given the
+// editor's current size
+fn import(s: &mut Cursive, f: impl FnOnce(&App, (usize, usize)) -> Array2<bool>) {
+    let Some(dim) = s.call_on_name(EDITOR, |canvas: &mut Canvas<Editor>| {
+        canvas.state_mut().bitmap.dim()
+    }) else {
+        return;
+    };
+    if let Some(bitmap) = s.with_user_data(|app: &mut App| f(app, dim)) {
+        s.call_on_name(EDITOR, |canvas: &mut Canvas<Editor>| {
+            canvas.state_mut().load(bitmap);
+        });
+    }
+}
+
+// Ask for a name to save the bitmap under
+fn prompt_save(s: &mut Cursive) {
+    s.add_layer(
+        Dialog::around(
+            EditView::new()
+                .on_submit(save)
+                .with_name(NAME)
+                .fixed_width(24),
+        )
+        .title("save to the palette as")
+        .button("Save", |s| {
+            let name = s
+                .call_on_name(NAME, |field: &mut EditView| field.get_content())
+                .expect("the save prompt has a name field");
+            save(s, &name);
+        })
+        .dismiss_button("Cancel"),
+    );
+}
+
+// Save the bitmap under `name`, give it to the player and close the editor,
+// or explain why it could not be saved
+fn save(s: &mut Cursive, name: &str) {
+    let Some(bitmap) = s.call_on_name(EDITOR, |canvas: &mut Canvas<Editor>| {
+        canvas.state_mut().bitmap().clone()
+    }) else {
+        return;
+    };
+    let result = s.with_user_data(|app: &mut App| -> Result<(), Box<dyn Error>> {
+        let path = app.palette.save(name, bitmap.clone())?;
+        app.game_state.player.bitmap = bitmap;
+        app.game_state.clamp_player();
+        app.message = Some(format!("saved {} to {}", name, path.display()));
+        Ok(())
+    });
+    match result {
+        Some(Err(e)) => s.add_layer(Dialog::info(format!("not saved: {}", e))),
+        _ => {
+            // The prompt, then the editor
+            s.pop_layer();
+            s.pop_layer();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resize_keeps_pixels_and_limits() {
+        let mut editor = Editor::new(array![[true, false], [false, true]], (3, 4));
+        editor.resize(Direction::Right);
+        editor.resize(Direction::Down);
+        editor.resize(Direction::Down);
+        assert_eq!(
+            editor.bitmap(),
+            array![
+                [true, false, false],
+                [false, true, false],
+                [false, false, false]
+            ]
+        );
+
+        editor.move_cursor(Direction::DownRight);
+        editor.move_cursor(Direction::DownRight);
+        editor.toggle();
+        assert!(editor.bitmap()[[2, 2]]);
+        // Shrinking pulls the cursor back onto the bitmap
+        editor.resize(Direction::Up);
+        editor.resize(Direction::UpLeft);
+        assert_eq!(editor.bitmap(), array![[true, false]]);
+        assert_eq!(editor.cursor, (0, 1));
+        editor.resize(Direction::Up);
+        assert_eq!(editor.bitmap().dim(), (1, 2));
+    }
+
+    #[test]
+    fn test_load_crops_to_the_site_layer() {
+        let mut editor = Editor::new(Array2::from_elem((5, 5), true), (4, 3));
+        assert_eq!(editor.bitmap().dim(), (4, 3));
+        editor.clear();
+        assert!(!editor.bitmap().iter().any(|&cell| cell));
+    }
+}
@@ -0,0 +1,516 @@
+use crate::bitmap_loader::NamedBitmap;
+use crate::config::{offset_along, Boundary};
+use crate::player::{Direction, FAST_MOVE};
+use crate::state::{GameState, PlacementError, Point2};
+use cursive::event::{Event, Key};
+use cursive::theme::{Color, ColorStyle};
+use cursive::traits::Scrollable;
+use cursive::{
+    views::{Canvas, LinearLayout, NamedView, OnEventView, TextView},
+    Cursive, Printer, Vec2,
+};
+use ndarray::*;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+mod editor;
+mod palette;
+mod playback;
+mod sites;
+mod stats;
+mod view;
+
+use palette::Palette;
+use playback::Playback;
+use sites::{cursor, overlays, site_table, Cursor, SiteOverlay};
+use stats::Stats;
+use view::View;
+
+// Target time per frame, about 30 frames a second
+const FRAME_TIME: Duration = Duration::from_millis(33);
+// Part of each frame spent stepping; the rest handles input and drawing
+const STEP_BUDGET: Duration = Duration::from_millis(25);
+// Steps between clock reads while running a chunk
+const CLOCK_STRIDE: usize = 1024;
+
+// Characters for increasing intensity, from an empty cell to a full one
+const SHADES: [&str; 5] = [".", "░", "▒", "▓", "█"];
+
+// What the cursive callbacks share with the simulation loop
+struct App {
+    game_state: GameState,
+    view: View,
+    // Whether to draw the target patterns of sites where the grid misses them
+    ghosts: bool,
+    stats: Stats,
+    playback: Playback,
+    // Feedback on the last action, cleared when the player moves
+    message: Option<String>,
+    // Index of the site the site commands act on
+    selected: Option<usize>,
+    palette: Palette,
+}
+
+// Everything the canvas needs to draw one frame
+struct Frame {
+    image: Array2<f32>,
+    sites: Vec<SiteOverlay>,
+    ghosts: bool,
+    // Only shown when the image passes through the site layer
+    cursor: Option<Cursor>,
+    // Whether the image wraps around along its rows and columns
+    wrap: (bool, bool),
+}
+
+impl App {
+    // Take up to a frame's worth of steps, stopping early at `deadline`
+    fn run_chunk(&mut self, deadline: Instant) {
+        let steps_per_frame = self.game_state.config.display_update_interval;
+        for i in 1..=steps_per_frame {
+            if !self.playback.take_step(self.game_state.get_step_count()) {
+                break;
+            }
+            self.game_state.step();
+            if i % CLOCK_STRIDE == 0 && Instant::now() >= deadline {
+                break;
+            }
+        }
+    }
+}
+
+impl Frame {
+    fn new(app: &App) -> Frame {
+        let projection = app.view.projection();
+        let boundaries = app.game_state.config.boundaries();
+        let (row_axis, col_axis) = projection.axes();
+        Frame {
+            image: app.game_state.get_render_projection(projection),
+            sites: overlays(&app.game_state, projection, app.selected),
+            ghosts: app.ghosts,
+            cursor: cursor(&app.game_state, projection),
+            wrap: (
+                boundaries[row_axis] == Boundary::Periodic,
+                boundaries[col_axis] == Boundary::Periodic,
+            ),
+        }
+    }
+
+    // Image pixel at offset (r, c) from `corner`, wrapping around periodic
+    // edges; None past any other edge
+    fn pixel(&self, corner: Point2, r: usize, c: usize) -> Option<Point2> {
+        let (rows, cols) = self.image.dim();
+        Some((
+            offset_along(corner.0, r, rows, self.wrap.0)?,
+            offset_along(corner.1, c, cols, self.wrap.1)?,
+        ))
+    }
+}
+
+/// Run the interactive cursive frontend until the user presses 'q'
+///
+/// The simulation stops advancing after `max_steps` steps (if given) but the
+/// view stays open. The player can switch to any bitmap in `library` for the
+/// sites it forces, and bitmaps drawn in the editor are saved to
+/// `palette_dir`. Returns the final state once the user quits.
+pub fn run_sim(
+    game_state: GameState,
+    max_steps: Option<usize>,
+    library: Vec<NamedBitmap>,
+    palette_dir: PathBuf,
+) -> GameState {
+    // Initialize visualization with cursive
+    let siv = cursive::default();
+    let mut siv = siv.into_runner();
+
+    let palette = Palette::new(game_state.player.bitmap.clone(), library, palette_dir);
+    let app = App {
+        stats: Stats::new(&game_state),
+        game_state,
+        view: View::default(),
+        ghosts: false,
+        playback: Playback::new(max_steps),
+        message: None,
+        selected: None,
+        palette,
+    };
+
+    // Create Canvas with initial state and player position
+    let canvas = Canvas::new(Frame::new(&app))
+        .with_draw(|frame: &Frame, printer: &Printer| {
+            // Draw the grid
+            for (pos, value) in frame.image.indexed_iter() {
+                printer.print((pos.1, pos.0), shade(*value));
+            }
+
+            for site in &frame.sites {
+                draw_site(frame, site, printer);
+            }
+
+            if let Some(cursor) = &frame.cursor {
+                draw_cursor(frame, cursor, printer);
+            }
+        })
+        .with_required_size(|frame, _| {
+            let (rows, cols) = frame.image.dim();
+            Vec2::new(cols, rows)
+        });
+
+    let mut screen = OnEventView::new(
+        LinearLayout::horizontal()
+            .child(
+                LinearLayout::vertical()
+                    .child(NamedView::new("canvas", canvas))
+                    .child(NamedView::new(
+                        "stats",
+                        TextView::new(stats_panel(&app, &Frame::new(&app))),
+                    )),
+            )
+            .child(
+                LinearLayout::vertical()
+                    .child(
+                        NamedView::new(
+                            "sites",
+                            TextView::new(site_table(&app.game_state, app.selected)),
+                        )
+                        .scrollable(),
+                    )
+                    .child(NamedView::new(
+                        "palette",
+                        TextView::new(app.palette.listing()),
+                    )),
+            ),
+    );
+    // Keys are bound as pre-event callbacks on the main screen so the
+    // scrollable site panel never swallows them, while dialogs opened on top
+    // of it get their own keys
+    screen.set_on_pre_event('q', |s| s.quit());
+
+    siv.set_user_data(app);
+
+    // WASD or the arrow keys move the player, Home, PageUp, End and PageDown
+    // (7, 9, 1 and 3 on the keypad) move it diagonally, and with shift every
+    // move goes FAST_MOVE cells
+    let moves = [
+        (Event::Char('w'), Event::Char('W'), Key::Up, Direction::Up),
+        (
+            Event::Char('a'),
+            Event::Char('A'),
+            Key::Left,
+            Direction::Left,
+        ),
+        (
+            Event::Char('s'),
+            Event::Char('S'),
+            Key::Down,
+            Direction::Down,
+        ),
+        (
+            Event::Char('d'),
+            Event::Char('D'),
+            Key::Right,
+            Direction::Right,
+        ),
+    ];
+    for (key, fast_key, arrow, direction) in moves {
+        for (event, distance) in [
+            (key, 1),
+            (fast_key, FAST_MOVE),
+            (Event::Key(arrow), 1),
+            (Event::Shift(arrow), FAST_MOVE),
+        ] {
+            screen.set_on_pre_event(event, move |s| move_player(s, direction, distance));
+        }
+    }
+    let diagonals = [
+        (Key::Home, Direction::UpLeft),
+        (Key::PageUp, Direction::UpRight),
+        (Key::End, Direction::DownLeft),
+        (Key::PageDown, Direction::DownRight),
+    ];
+    for (key, direction) in diagonals {
+        screen.set_on_pre_event(key, move |s| move_player(s, direction, 1));
+        screen.set_on_pre_event(Event::Shift(key), move |s| {
+            move_player(s, direction, FAST_MOVE);
+        });
+    }
+
+    // press enter to force a new site at player, unless the cursor is blocked
+    screen.set_on_pre_event(Key::Enter, |s| {
+        with_app(s, |app| {
+            app.message = match app.game_state.force_site() {
+                Ok(()) => Some("site placed".to_string()),
+                Err(e) => Some(format!("site not placed: {}", e)),
+            };
+        });
+    });
+
+    // 'v' cycles projections, '[' and ']' page through the sliced axis, '{'
+    // and '}' ten planes at a time
+    screen.set_on_pre_event('v', |s| with_app(s, |app| app.view.cycle()));
+    // 'g' shows where the grid still differs from each site's target
+    screen.set_on_pre_event('g', |s| with_app(s, |app| app.ghosts = !app.ghosts));
+    let paging = [
+        (Event::Char('['), -1),
+        (Event::Char(']'), 1),
+        (Event::Char('{'), -10),
+        (Event::Char('}'), 10),
+    ];
+    for (key, delta) in paging {
+        screen.set_on_pre_event(key, move |s| {
+            with_app(s, |app| {
+                app.view.page(delta, app.game_state.config.grid_size);
+            });
+        });
+    }
+
+    // 'e' selects the site under the player and Tab cycles through all sites;
+    // Delete removes the selected site, 'm' moves it to the player, 'r'
+    // reactivates it and 't' gives it the player's bitmap
+    screen.set_on_pre_event('e', |s| {
+        with_app(s, |app| {
+            app.selected = app.game_state.find_site_under_player();
+            app.message = Some(match app.selected {
+                Some(index) => format!("selected site {}", index),
+                None => "no site under the player".to_string(),
+            });
+        });
+    });
+    for (key, forward) in [
+        (Event::Key(Key::Tab), true),
+        (Event::Shift(Key::Tab), false),
+    ] {
+        screen.set_on_pre_event(key, move |s| {
+            with_app(s, |app| {
+                let count = app.game_state.sites.total_count();
+                app.selected = match (app.selected, forward) {
+                    _ if count == 0 => None,
+                    (None, true) => Some(0),
+                    (None, false) => Some(count - 1),
+                    (Some(i), true) => Some((i + 1) % count),
+                    (Some(i), false) => Some((i + count - 1) % count),
+                };
+            });
+        });
+    }
+    screen.set_on_pre_event(Key::Esc, |s| with_app(s, |app| app.selected = None));
+    for key in [Key::Del, Key::Backspace] {
+        screen.set_on_pre_event(key, |s| {
+            with_selected(s, |app, index| {
+                let site = app.game_state.remove_site(index);
+                app.selected = None;
+                let (x, y) = site.position;
+                Ok(format!("removed site {} at {},{}", index, x, y))
+            });
+        });
+    }
+    screen.set_on_pre_event('m', |s| {
+        with_selected(s, |app, index| {
+            let position = app.game_state.player.position;
+            app.game_state.move_site(index, position)?;
+            Ok(format!(
+                "moved site {} to {},{}",
+                index, position.0, position.1
+            ))
+        });
+    });
+    screen.set_on_pre_event('r', |s| {
+        with_selected(s, |app, index| {
+            app.game_state.reactivate_site(index)?;
+            Ok(format!("reactivated site {}", index))
+        });
+    });
+    screen.set_on_pre_event('t', |s| {
+        with_selected(s, |app, index| {
+            let bitmap = app.game_state.player.bitmap.clone();
+            app.game_state.retarget_site(index, bitmap)?;
+            Ok(format!("site {} now targets the player's bitmap", index))
+        });
+    });
+
+    // 'p' and 'P' pick the next or previous palette bitmap for the player
+    for (key, forward) in [('p', true), ('P', false)] {
+        screen.set_on_pre_event(key, move |s| {
+            with_app(s, |app| {
+                let entry = app.palette.cycle(forward);
+                app.game_state.player.bitmap = entry.bitmap.clone();
+                app.game_state.clamp_player();
+                app.message = Some(format!("placing {}", entry.name));
+            });
+        });
+    }
+
+    // 'b' opens the bitmap editor
+    screen.set_on_pre_event('b', editor::open);
+
+    // Space pauses, 'n' takes a step (or as many as a typed count, as in
+    // "25n"), '+' and '-' change how many steps run between frames
+    screen.set_on_pre_event(' ', |s| with_app(s, |app| app.playback.toggle_pause()));
+    screen.set_on_pre_event('n', |s| with_app(s, |app| app.playback.queue_steps()));
+    for digit in 0..10 {
+        let key = char::from_digit(digit, 10).expect("digit is below 10");
+        screen.set_on_pre_event(key, move |s| {
+            with_app(s, |app| app.playback.push_digit(digit));
+        });
+    }
+    for (key, faster) in [('+', true), ('=', true), ('-', false)] {
+        screen.set_on_pre_event(key, move |s| {
+            with_app(s, |app| {
+                let interval = &mut app.game_state.config.display_update_interval;
+                *interval = if faster {
+                    interval.saturating_mul(2)
+                } else {
+                    (*interval / 2).max(1)
+                };
+            });
+        });
+    }
+
+    siv.add_layer(screen);
+    siv.refresh();
+
+    while siv.is_running() {
+        let frame_start = Instant::now();
+        // Handle input first so it never waits on the simulation
+        siv.process_events();
+
+        let render_data = siv.with_user_data(|app: &mut App| {
+            app.run_chunk(frame_start + STEP_BUDGET);
+            app.stats.sample(&app.game_state);
+            let frame = Frame::new(app);
+            let stats = stats_panel(app, &frame);
+            let table = site_table(&app.game_state, app.selected);
+            (frame, stats, table, app.palette.listing())
+        });
+
+        if let Some((frame, stats, table, palette)) = render_data {
+            if let Some(mut canvas) = siv.find_name::<Canvas<Frame>>("canvas") {
+                *canvas.state_mut() = frame;
+            }
+            if let Some(mut text) = siv.find_name::<TextView>("stats") {
+                text.set_content(stats);
+            }
+            if let Some(mut text) = siv.find_name::<TextView>("sites") {
+                text.set_content(table);
+            }
+            if let Some(mut text) = siv.find_name::<TextView>("palette") {
+                text.set_content(palette);
+            }
+            siv.refresh();
+        }
+
+        // Sleep out the rest of the frame, also while paused
+        thread::sleep(FRAME_TIME.saturating_sub(frame_start.elapsed()));
+    }
+
+    siv.take_user_data::<App>()
+        .expect("app is set before the loop")
+        .game_state
+}
+
+// Character for an intensity in [0, 1]
+fn shade(value: f32) -> &'static str {
+    SHADES[(value * (SHADES.len() - 1) as f32).round() as usize]
+}
+
+// Outline a site in its colour and, with ghosts on, show its target wherever
+// the grid differs from it
+fn draw_site(frame: &Frame, site: &SiteOverlay, printer: &Printer) {
+    let (height, width) = site.target.dim();
+    let style = ColorStyle::front(site.kind.color());
+    // The selected site's outline is drawn in reverse
+    let edge_style = if site.selected {
+        ColorStyle::new(Color::Rgb(0, 0, 0), site.kind.color())
+    } else {
+        style
+    };
+    for ((r, c), target) in site.target.indexed_iter() {
+        let Some(pos) = frame.pixel(site.corner, r, c) else {
+            continue;
+        };
+        let actual = frame.image[pos];
+        let edge = r == 0 || c == 0 || r == height - 1 || c == width - 1;
+        let (ch, style) = if frame.ghosts && shade(*target) != shade(actual) {
+            (shade(*target), if edge { edge_style } else { style })
+        } else if edge {
+            (shade(actual), edge_style)
+        } else {
+            continue;
+        };
+        printer.with_color(style, |printer| printer.print((pos.1, pos.0), ch));
+    }
+}
+
+// Draw the player's bitmap over the grid as a translucent cursor: the cells
+// underneath stay visible on a magenta background, or red where a site
+// cannot be placed
+fn draw_cursor(frame: &Frame, cursor: &Cursor, printer: &Printer) {
+    let background = match cursor.blocked {
+        None => Color::Rgb(150, 0, 150),
+        Some(_) => Color::Rgb(200, 0, 0),
+    };
+    let style = ColorStyle::new(Color::Rgb(255, 255, 255), background);
+    for ((r, c), value) in cursor.footprint.indexed_iter() {
+        // Always mark the player position itself
+        if *value == 0.0 && (r, c) != (0, 0) {
+            continue;
+        }
+        if let Some(pos) = frame.pixel(cursor.corner, r, c) {
+            let ch = shade(frame.image[pos]);
+            printer.with_color(style, |printer| printer.print((pos.1, pos.0), ch));
+        }
+    }
+}
+
+// Move the player and clear the message about its last placement
+fn move_player(s: &mut Cursive, direction: Direction, distance: usize) {
+    with_app(s, |app| {
+        app.game_state.move_player(direction, distance);
+        app.message = None;
+    });
+}
+
+// Run a command on the selected site and report how it went
+fn with_selected(
+    s: &mut Cursive,
+    f: impl FnOnce(&mut App, usize) -> Result<String, PlacementError>,
+) {
+    with_app(s, |app| {
+        app.message = Some(match app.selected {
+            Some(index) => match f(app, index) {
+                Ok(message) => message,
+                Err(e) => format!("site {} unchanged: {}", index, e),
+            },
+            None => "no site selected (press e or Tab)".to_string(),
+        });
+    });
+}
+
+// Run `f` on the shared app state
+fn with_app(s: &mut Cursive, f: impl FnOnce(&mut App)) {
+    s.with_user_data(f);
+}
+
+// Run statistics shown under the grid
+fn stats_panel(app: &App, frame: &Frame) -> String {
+    let fill = frame.image.mean().unwrap_or(0.0) as f64;
+    let game_state = &app.game_state;
+    let (x, y) = game_state.player.position;
+    let placement = match game_state.check_player_site() {
+        Ok(()) => "free".to_string(),
+        Err(e) => format!("blocked, {}", e),
+    };
+    let mut panel = format!(
+        "{}\nframe       up to {} steps\nplayback    {}\ncursor      {},{} {}",
+        app.stats.panel(game_state, app.view.projection(), fill),
+        game_state.config.display_update_interval,
+        app.playback.describe(game_state.get_step_count()),
+        x,
+        y,
+        placement
+    );
+    if let Some(message) = &app.message {
+        panel += &format!("\n{}", message);
+    }
+    panel
+}
@@ -0,0 +1,136 @@
+use crate::bitmap_loader::{save_bitmap_as_bmp, NamedBitmap};
+use ndarray::*;
+use std::error::Error;
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+
+/// The bitmaps the player can place, starting with the one the run began with
+pub struct Palette {
+    entries: Vec<NamedBitmap>,
+    current: usize,
+    // Where bitmaps drawn in the editor are saved
+    dir: PathBuf,
+}
+
+impl Palette {
+    pub fn new(player_bitmap: Array2<bool>, library: Vec<NamedBitmap>, dir: PathBuf) -> Palette {
+        let mut entries = vec![NamedBitmap {
+            name: "player".to_string(),
+            bitmap: player_bitmap,
+        }];
+        entries.extend(library);
+        Palette {
+            entries,
+            current: 0,
+            dir,
+        }
+    }
+
+    pub fn current(&self) -> &NamedBitmap {
+        &self.entries[self.current]
+    }
+
+    /// Select the next (or previous) bitmap, wrapping around
+    pub fn cycle(&mut self, forward: bool) -> &NamedBitmap {
+        let len = self.entries.len();
+        self.current = if forward {
+            (self.current + 1) % len
+        } else {
+            (self.current + len - 1) % len
+        };
+        self.current()
+    }
+
+    /// Save `bitmap` as `<name>.bmp` in the palette directory and select it,
+    /// replacing any library bitmap of the same name; returns the file written
+    pub fn save(&mut self, name: &str, bitmap: Array2<bool>) -> Result<PathBuf, Box<dyn Error>> {
+        let valid = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+        if name.is_empty() || !name.chars().all(valid) {
+            return Err("names may only use letters, digits, '_' and '-'".into());
+        }
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(format!("{}.bmp", name));
+        save_bitmap_as_bmp(&bitmap, &path)?;
+
+        let entry = NamedBitmap {
+            name: name.to_string(),
+            bitmap,
+        };
+        // Entry 0 is the starting bitmap, never replaced
+        match self.entries[1..].iter().position(|e| e.name == name) {
+            Some(i) => {
+                self.current = i + 1;
+                self.entries[self.current] = entry;
+            }
+            None => {
+                self.entries.push(entry);
+                self.current = self.entries.len() - 1;
+            }
+        }
+        Ok(path)
+    }
+
+    /// One line per bitmap with its size, the current one marked
+    pub fn listing(&self) -> String {
+        let mut listing = String::from("palette (p / P)\n");
+        for (i, entry) in self.entries.iter().enumerate() {
+            let (rows, cols) = entry.bitmap.dim();
+            let _ = writeln!(
+                listing,
+                "{} {:>2} {:<20} {}x{}",
+                if i == self.current { ">" } else { " " },
+                i,
+                entry.name,
+                rows,
+                cols
+            );
+        }
+        listing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitmap_loader::load_bitmap_library;
+
+    #[test]
+    fn test_cycle_wraps_around() {
+        let square = NamedBitmap {
+            name: "square".to_string(),
+            bitmap: Array2::from_elem((2, 2), true),
+        };
+        let mut palette = Palette::new(
+            Array2::from_elem((3, 3), true),
+            vec![square],
+            PathBuf::from("unused"),
+        );
+        assert_eq!(palette.current().name, "player");
+        assert_eq!(palette.cycle(true).name, "square");
+        assert_eq!(palette.cycle(true).name, "player");
+        assert_eq!(palette.cycle(false).name, "square");
+        assert!(palette.listing().contains(">  1 square"));
+    }
+
+    #[test]
+    fn test_saved_bitmaps_join_the_palette() {
+        let dir = std::env::temp_dir().join(format!("anscombe-palette-{}", std::process::id()));
+        let mut palette = Palette::new(Array2::from_elem((3, 3), true), vec![], dir.clone());
+        let bitmap = array![[true, false, true], [false, true, false]];
+
+        assert!(palette.save("bad name", bitmap.clone()).is_err());
+        let path = palette.save("zigzag", bitmap.clone()).unwrap();
+        assert_eq!(palette.current().name, "zigzag");
+        // Saving again under the same name replaces the entry
+        palette.save("zigzag", bitmap.clone()).unwrap();
+        assert!(palette
+            .listing()
+            .ends_with(">  1 zigzag               2x3\n"));
+
+        let library = load_bitmap_library(&dir);
+        assert_eq!(library[0].bitmap, bitmap);
+        fs::remove_file(path).unwrap();
+        fs::remove_dir(dir).unwrap();
+    }
+}
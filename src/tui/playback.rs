@@ -0,0 +1,114 @@
+/// Whether the simulation runs freely, is paused, or is working off steps
+/// requested while paused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Playback {
+    paused: bool,
+    // Steps still to take while paused
+    pending: usize,
+    // Count typed before a step key, as in "25n"
+    count: Option<usize>,
+    max_steps: Option<usize>,
+}
+
+impl Playback {
+    pub fn new(max_steps: Option<usize>) -> Playback {
+        Playback {
+            paused: false,
+            pending: 0,
+            count: None,
+            max_steps,
+        }
+    }
+
+    /// Pause, or resume free running (dropping any queued steps)
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.pending = 0;
+        self.count = None;
+    }
+
+    /// Append a digit to the typed count
+    pub fn push_digit(&mut self, digit: u32) {
+        let count = self.count.unwrap_or(0);
+        self.count = Some(count.saturating_mul(10).saturating_add(digit as usize));
+    }
+
+    /// Pause and queue the typed count of steps, or a single step
+    pub fn queue_steps(&mut self) {
+        self.paused = true;
+        self.pending = self.pending.saturating_add(self.count.take().unwrap_or(1));
+    }
+
+    /// Whether to take a step now that `step` steps are done; consumes a
+    /// queued step when paused
+    pub fn take_step(&mut self, step: usize) -> bool {
+        if self.at_step_limit(step) {
+            return false;
+        }
+        if self.paused {
+            if self.pending == 0 {
+                return false;
+            }
+            self.pending -= 1;
+        }
+        true
+    }
+
+    fn at_step_limit(&self, step: usize) -> bool {
+        self.max_steps.is_some_and(|max| step >= max)
+    }
+
+    /// Short description for the stats panel
+    pub fn describe(&self, step: usize) -> String {
+        let mut text = if self.at_step_limit(step) {
+            "stopped at step limit".to_string()
+        } else if !self.paused {
+            "running".to_string()
+        } else if self.pending > 0 {
+            format!("paused, {} steps queued", self.pending)
+        } else {
+            "paused".to_string()
+        };
+        if let Some(count) = self.count {
+            text += &format!(" (count {})", count);
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queued_steps_run_while_paused() {
+        let mut playback = Playback::new(None);
+        assert!(playback.take_step(0));
+
+        playback.toggle_pause();
+        assert!(!playback.take_step(0));
+
+        playback.push_digit(1);
+        playback.push_digit(2);
+        playback.queue_steps();
+        assert_eq!(playback.describe(0), "paused, 12 steps queued");
+        let taken = (0..20).filter(|&step| playback.take_step(step)).count();
+        assert_eq!(taken, 12);
+        assert_eq!(playback.describe(20), "paused");
+
+        // Without a count a step key queues one step
+        playback.queue_steps();
+        assert!(playback.take_step(20));
+        assert!(!playback.take_step(21));
+    }
+
+    #[test]
+    fn test_step_limit_stops_everything() {
+        let mut playback = Playback::new(Some(5));
+        assert!(playback.take_step(4));
+        assert!(!playback.take_step(5));
+        playback.queue_steps();
+        assert!(!playback.take_step(5));
+        assert_eq!(playback.describe(5), "stopped at step limit");
+    }
+}
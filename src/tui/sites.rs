@@ -0,0 +1,140 @@
+use crate::projection::Projection;
+use crate::site::Site;
+use crate::state::{GameState, PlacementError, Point2, Point3};
+use cursive::theme::Color;
+use ndarray::*;
+use std::fmt::Write;
+
+/// How a site is coloured on the canvas and labelled in the panel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SiteKind {
+    Active,
+    Completed,
+    /// Still active and placed by the player with its own bitmap
+    Custom,
+}
+
+impl SiteKind {
+    pub fn of(site: &Site) -> SiteKind {
+        if !site.is_active {
+            SiteKind::Completed
+        } else if site.custom_bitmap.is_some() {
+            SiteKind::Custom
+        } else {
+            SiteKind::Active
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            SiteKind::Active => Color::Rgb(0, 200, 0),
+            SiteKind::Completed => Color::Rgb(80, 120, 255),
+            SiteKind::Custom => Color::Rgb(255, 200, 0),
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            SiteKind::Active => "active",
+            SiteKind::Completed => "completed",
+            SiteKind::Custom => "custom",
+        }
+    }
+}
+
+/// The visible part of one site in the current projection
+pub struct SiteOverlay {
+    /// Image pixel of the site's corner
+    pub corner: Point2,
+    /// The site's target pattern seen through the same projection
+    pub target: Array2<f32>,
+    pub kind: SiteKind,
+    pub selected: bool,
+}
+
+/// Overlays for every site the projection shows
+pub fn overlays(
+    game_state: &GameState,
+    projection: Projection,
+    selected: Option<usize>,
+) -> Vec<SiteOverlay> {
+    game_state
+        .sites
+        .get_all_sites()
+        .iter()
+        .enumerate()
+        .filter_map(|(i, site)| {
+            let pattern = site.get_pattern(&game_state.bmp);
+            let (corner, target) = project_box(game_state, projection, site.origin(), pattern)?;
+            Some(SiteOverlay {
+                corner,
+                target,
+                kind: SiteKind::of(site),
+                selected: selected == Some(i),
+            })
+        })
+        .collect()
+}
+
+/// The player's bitmap where a forced site would go
+pub struct Cursor {
+    /// Image pixel of the player position
+    pub corner: Point2,
+    pub footprint: Array2<f32>,
+    /// Why a site cannot be placed here, if it cannot
+    pub blocked: Option<PlacementError>,
+}
+
+/// The player's cursor, if the projection shows the site layer
+pub fn cursor(game_state: &GameState, projection: Projection) -> Option<Cursor> {
+    let bitmap = game_state.player.bitmap.view().insert_axis(Axis(2));
+    let (corner, footprint) =
+        project_box(game_state, projection, game_state.player_origin(), bitmap)?;
+    Some(Cursor {
+        corner,
+        footprint,
+        blocked: game_state.check_player_site().err(),
+    })
+}
+
+// A pattern at `origin` seen through `projection`, with the image pixel of its
+// corner, or None if the projection misses it
+fn project_box(
+    game_state: &GameState,
+    projection: Projection,
+    origin: Point3,
+    pattern: ArrayView3<bool>,
+) -> Option<(Point2, Array2<f32>)> {
+    let dims = game_state.config.grid_size.dim();
+    let (local, corner) = projection.clip(origin, pattern.dim(), dims)?;
+    Some((corner, local.render(&pattern.to_owned())))
+}
+
+/// One line per site: position, pattern size, goodness and age in steps, with
+/// the selected site marked
+pub fn site_table(game_state: &GameState, selected: Option<usize>) -> String {
+    let mut table = format!(
+        "  {:>3} {:<9} {:>12} {:>8} {:>6} {:>9}\n",
+        "#", "status", "position", "size", "good", "age"
+    );
+    for (i, site) in game_state.sites.get_all_sites().iter().enumerate() {
+        let (rows, cols, layers) = site.get_extent(&game_state.bmp);
+        let size = if layers == 1 {
+            format!("{}x{}", rows, cols)
+        } else {
+            format!("{}x{}x{}", rows, cols, layers)
+        };
+        let _ = writeln!(
+            table,
+            "{} {:>3} {:<9} {:>12} {:>8} {:>6.3} {:>9}",
+            if selected == Some(i) { ">" } else { " " },
+            i,
+            SiteKind::of(site).label(),
+            format!("{},{},{}", site.position.0, site.position.1, site.z),
+            size,
+            game_state.site_goodness(site),
+            game_state.get_step_count() - site.created_at
+        );
+    }
+    table
+}
@@ -0,0 +1,81 @@
+use crate::projection::Projection;
+use crate::state::GameState;
+use std::time::{Duration, Instant};
+
+// Shortest time rates are measured over, so redraws forced by input do not
+// report rates from a handful of steps
+const MIN_SAMPLE: Duration = Duration::from_millis(250);
+
+/// Step and acceptance rates measured between display updates
+pub struct Stats {
+    sampled_at: Instant,
+    step: usize,
+    proposed: usize,
+    accepted: usize,
+    steps_per_second: f64,
+    // None until a sample contains a proposed move
+    acceptance_rate: Option<f64>,
+}
+
+impl Stats {
+    pub fn new(game_state: &GameState) -> Stats {
+        Stats {
+            sampled_at: Instant::now(),
+            step: game_state.get_step_count(),
+            proposed: game_state.get_proposed_moves(),
+            accepted: game_state.get_accepted_moves(),
+            steps_per_second: 0.0,
+            acceptance_rate: None,
+        }
+    }
+
+    /// Update the rates with the steps taken since the last sample
+    pub fn sample(&mut self, game_state: &GameState) {
+        let elapsed = self.sampled_at.elapsed();
+        if elapsed < MIN_SAMPLE {
+            return;
+        }
+        let (step, proposed, accepted) = (
+            game_state.get_step_count(),
+            game_state.get_proposed_moves(),
+            game_state.get_accepted_moves(),
+        );
+        self.steps_per_second = (step - self.step) as f64 / elapsed.as_secs_f64();
+        if proposed > self.proposed {
+            self.acceptance_rate =
+                Some((accepted - self.accepted) as f64 / (proposed - self.proposed) as f64);
+        }
+        self.sampled_at = Instant::now();
+        self.step = step;
+        self.proposed = proposed;
+        self.accepted = accepted;
+    }
+
+    /// The panel shown under the grid; `fill` is the mean intensity of the
+    /// image `projection` currently shows
+    pub fn panel(&self, game_state: &GameState, projection: Projection, fill: f64) -> String {
+        let acceptance = match self.acceptance_rate {
+            Some(rate) => format!("{:.1}%", rate * 100.0),
+            None => "-".to_string(),
+        };
+        format!(
+            "step        {}\n\
+             steps/s     {:.0}\n\
+             sites       {} active / {} total\n\
+             completions {}\n\
+             accepted    {} of moves\n\
+             fill        {:.4}\n\
+             T           {:.4}\n\
+             view        {}",
+            game_state.get_step_count(),
+            self.steps_per_second,
+            game_state.sites.active_count(),
+            game_state.sites.total_count(),
+            game_state.get_completions().len(),
+            acceptance,
+            fill,
+            game_state.get_temperature(),
+            projection
+        )
+    }
+}
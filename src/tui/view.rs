@@ -0,0 +1,77 @@
+use crate::config::GridSize;
+use crate::projection::Projection;
+
+/// Kinds of image the canvas can show, in the order 'v' cycles through them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Layer,
+    Max,
+    Mean,
+    XzSection,
+    YzSection,
+}
+
+impl Mode {
+    fn next(self) -> Mode {
+        match self {
+            Mode::Layer => Mode::Max,
+            Mode::Max => Mode::Mean,
+            Mode::Mean => Mode::XzSection,
+            Mode::XzSection => Mode::YzSection,
+            Mode::YzSection => Mode::Layer,
+        }
+    }
+}
+
+/// Which projection of the grid the canvas shows
+///
+/// Each sliced mode remembers its own position, so cycling away and back
+/// returns to the same plane.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct View {
+    mode: Mode,
+    z: usize,
+    y: usize,
+    x: usize,
+}
+
+impl Default for View {
+    fn default() -> Self {
+        View {
+            mode: Mode::Layer,
+            z: 0,
+            y: 0,
+            x: 0,
+        }
+    }
+}
+
+impl View {
+    pub fn projection(&self) -> Projection {
+        match self.mode {
+            Mode::Layer => Projection::Layer(self.z),
+            Mode::Max => Projection::Max,
+            Mode::Mean => Projection::Mean,
+            Mode::XzSection => Projection::XzSection(self.y),
+            Mode::YzSection => Projection::YzSection(self.x),
+        }
+    }
+
+    /// Switch to the next projection mode
+    pub fn cycle(&mut self) {
+        self.mode = self.mode.next();
+    }
+
+    /// Move the sliced plane by `delta`, staying inside the grid
+    ///
+    /// Does nothing for the projections over all of z.
+    pub fn page(&mut self, delta: isize, grid_size: GridSize) {
+        let (index, len) = match self.mode {
+            Mode::Layer => (&mut self.z, grid_size.z),
+            Mode::XzSection => (&mut self.y, grid_size.y),
+            Mode::YzSection => (&mut self.x, grid_size.x),
+            Mode::Max | Mode::Mean => return,
+        };
+        *index = index.saturating_add_signed(delta).min(len - 1);
+    }
+}